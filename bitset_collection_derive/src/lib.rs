@@ -0,0 +1,64 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Index};
+
+/// Derive `BitSetKey` for a single-field struct by delegating to its field.
+///
+/// `#[derive(BitSetKey)] struct EntityId(u32);`
+#[proc_macro_derive(BitSetKey)]
+pub fn derive_bitset_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Error::new(
+                Span::call_site(),
+                "BitSetKey can only be derived for structs",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
+    if fields.len() != 1 {
+        return Error::new(
+            Span::call_site(),
+            "BitSetKey can only be derived for structs with exactly one field",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let field = fields.iter().next().unwrap();
+    let field_ty = &field.ty;
+
+    let (access, construct) = match fields {
+        Fields::Named(_) => {
+            let ident = field.ident.as_ref().unwrap();
+            (quote!(self.#ident), quote!(#name { #ident: inner }))
+        }
+        _ => {
+            let index = Index::from(0);
+            (quote!(self.#index), quote!(#name(inner)))
+        }
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::bitset_collection::BitSetKey for #name #ty_generics #where_clause {
+            fn to_index(self) -> Option<u32> {
+                <#field_ty as ::bitset_collection::BitSetKey>::to_index(#access)
+            }
+
+            fn try_from_index(index: u32) -> Option<Self> {
+                let inner = <#field_ty as ::bitset_collection::BitSetKey>::try_from_index(index)?;
+                Some(#construct)
+            }
+        }
+    };
+
+    expanded.into()
+}
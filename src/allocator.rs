@@ -0,0 +1,233 @@
+use core::ops::Deref;
+
+use hibitset::{BitSet, BitSetNot};
+
+use collection_trait::Collection;
+
+use crate::{
+    key_index, navigation::next_index_linear, BitSetCollection, BitSetCollectionError, BitSetKey,
+    BitSetMask, CollectionMut, StableCollection, BITSET_CAPACITY,
+};
+
+/// `BitSetCollection` wrapper that inserts values at the lowest key absent from its bitset.
+///
+/// The bitset is the only record of which keys are in use, so keys freed by `remove` are handed out again
+/// without a separate free list. Every index below the allocator's search hint is known to be occupied.
+///
+/// Reads go through `Deref` to the wrapped collection.
+#[derive(Debug, Clone)]
+pub struct KeyAllocator<'a, K, C, M = BitSet>
+where
+    C: Collection<'a, K>,
+{
+    collection: BitSetCollection<'a, K, C, M>,
+    lowest_free: u32,
+}
+
+impl<'a, K, C, M> Default for KeyAllocator<'a, K, C, M>
+where
+    C: Collection<'a, K> + Default,
+    M: Default,
+{
+    fn default() -> Self {
+        KeyAllocator {
+            collection: Default::default(),
+            lowest_free: 0,
+        }
+    }
+}
+
+impl<'a, K, C, M> Deref for KeyAllocator<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+{
+    type Target = BitSetCollection<'a, K, C, M>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.collection
+    }
+}
+
+impl<'a, K, C, M> KeyAllocator<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetMask,
+{
+    /// Allocate keys around those already present in `collection`.
+    pub fn new(collection: BitSetCollection<'a, K, C, M>) -> Self {
+        KeyAllocator {
+            collection,
+            lowest_free: 0,
+        }
+    }
+
+    /// Return the wrapped collection.
+    pub fn into_inner(self) -> BitSetCollection<'a, K, C, M> {
+        self.collection
+    }
+}
+
+impl<'a, K, C, M> KeyAllocator<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetMask,
+{
+    /// Index and key of the lowest key absent from the bitset, advancing the search hint past occupied keys.
+    ///
+    /// Free indices that do not map to a key are skipped. Fails with `KeyOutOfRange` if the only free indices
+    /// left are beyond the key type's range, or `KeyBeyondCapacity` if every index is in use.
+    fn lowest_free_key(&mut self) -> Result<(u32, K), BitSetCollectionError> {
+        let mask = BitSetNot(&self.collection.bitset);
+        let lowest_free = next_index_linear(&mask, self.lowest_free, BITSET_CAPACITY);
+        self.lowest_free = lowest_free.unwrap_or(BITSET_CAPACITY);
+
+        let mut index =
+            lowest_free.ok_or(BitSetCollectionError::KeyBeyondCapacity(BITSET_CAPACITY))?;
+        loop {
+            if let Some(key) = K::try_from_index(index) {
+                return Ok((index, key));
+            }
+            index = next_index_linear(&mask, index + 1, BITSET_CAPACITY)
+                .ok_or(BitSetCollectionError::KeyOutOfRange)?;
+        }
+    }
+
+    /// Lowest key absent from the bitset, which the next `insert_next` will use.
+    pub fn next_free_key(&mut self) -> Option<K> {
+        self.lowest_free_key().ok().map(|(_, key)| key)
+    }
+}
+
+impl<'a, K, C, M> KeyAllocator<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: StableCollection<'a, K>,
+    M: BitSetMask,
+{
+    /// Insert `value` at the lowest key absent from the bitset, returning that key.
+    ///
+    /// Fails if every index is in use or beyond the key type's range,
+    /// or if the wrapped collection is unable to store the value.
+    pub fn try_insert_next(&mut self, value: C::Item) -> Result<K, BitSetCollectionError> {
+        let (index, key) = self.lowest_free_key()?;
+
+        self.collection.try_insert(key, value)?;
+        self.lowest_free = index + 1;
+        Ok(key)
+    }
+
+    /// Insert `value` at the lowest key absent from the bitset, returning that key.
+    ///
+    /// Panics under the same conditions as `try_insert_next` fails.
+    pub fn insert_next(&mut self, value: C::Item) -> K {
+        self.try_insert_next(value).unwrap()
+    }
+
+    /// Insert `value` at a specific key, returning the previous value if the key was present.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: C::Item,
+    ) -> Result<Option<C::Item>, BitSetCollectionError> {
+        self.collection.try_insert(key, value)
+    }
+
+    /// Insert `value` at a specific key, returning the previous value if the key was present.
+    ///
+    /// Panics under the same conditions as `try_insert` fails.
+    pub fn insert(&mut self, key: K, value: C::Item) -> Option<C::Item> {
+        self.try_insert(key, value).unwrap()
+    }
+
+    /// Remove and return the value at `key`, making the key available to `insert_next` again.
+    pub fn try_remove(&mut self, key: &K) -> Result<Option<C::Item>, BitSetCollectionError> {
+        let index = key_index(*key)?;
+        let value = self.collection.try_remove(key)?;
+        self.lowest_free = self.lowest_free.min(index);
+        Ok(value)
+    }
+
+    /// Remove and return the value at `key`, making the key available to `insert_next` again.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn remove(&mut self, key: &K) -> Option<C::Item> {
+        self.try_remove(key).unwrap()
+    }
+
+    /// Remove every key, making all of them available again.
+    pub fn clear(&mut self) {
+        self.collection.clear();
+        self.lowest_free = 0;
+    }
+}
+
+impl<'a, K, C, M> KeyAllocator<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Fetch a mutable reference to the value at `key`.
+    pub fn try_get_mut(&mut self, key: &K) -> Result<Option<&mut C::Item>, BitSetCollectionError> {
+        self.collection.try_get_mut(key)
+    }
+
+    /// Fetch a mutable reference to the value at `key`.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut C::Item> {
+        self.try_get_mut(key).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use collection_trait::Collection;
+
+    use crate::{BitSetBTreeMap, BitSetCollectionError, BitSetVec, KeyAllocator};
+
+    #[test]
+    fn allocate_lowest_free_key() {
+        let collection = vec![(0, 'a'), (1, 'b'), (3, 'd')]
+            .into_iter()
+            .collect::<BitSetVec<usize, char>>();
+        let mut allocator = KeyAllocator::new(collection);
+
+        assert_eq!(allocator.insert_next('c'), 2);
+        assert_eq!(allocator.insert_next('e'), 4);
+        assert_eq!(allocator.remove(&1), Some('b'));
+        assert_eq!(allocator.next_free_key(), Some(1));
+        assert_eq!(allocator.insert_next('B'), 1);
+        assert_eq!(allocator.insert_next('f'), 5);
+
+        assert_eq!(allocator.values().copied().collect::<String>(), "aBcdef");
+        assert!(allocator.contains_key(&5));
+
+        allocator.clear();
+        assert_eq!(allocator.insert_next('z'), 0);
+    }
+
+    #[test]
+    fn allocate_within_key_range() {
+        let mut allocator = KeyAllocator::new(BitSetBTreeMap::<NonZeroU32, char>::default());
+        assert_eq!(allocator.next_free_key(), NonZeroU32::new(1));
+        assert_eq!(allocator.insert_next('a'), NonZeroU32::new(1).unwrap());
+        assert_eq!(allocator.insert_next('b'), NonZeroU32::new(2).unwrap());
+
+        let mut allocator = KeyAllocator::new(BitSetBTreeMap::<u8, u8>::default());
+        for key in 0..=u8::MAX {
+            assert_eq!(allocator.insert_next(key), key);
+        }
+        assert_eq!(allocator.next_free_key(), None);
+        assert_eq!(
+            allocator.try_insert_next(0),
+            Err(BitSetCollectionError::KeyOutOfRange)
+        );
+
+        assert_eq!(allocator.remove(&7), Some(7));
+        assert_eq!(allocator.insert_next(70), 7);
+    }
+}
@@ -0,0 +1,278 @@
+use alloc::{
+    collections::{btree_map, BTreeMap, VecDeque},
+    vec::Vec,
+};
+use core::hash::Hash;
+use std::collections::{hash_map, HashMap};
+
+use collection_trait::Collection;
+
+use crate::{BitSetKey, StableCollection};
+
+/// Extension of `StableCollection` for types that can hand out mutable references to their values.
+///
+/// # Safety
+///
+/// Implementors must guarantee that `get_raw_mut` returns non-overlapping pointers for distinct keys,
+/// and that obtaining one does not invalidate pointers previously obtained from the same `Raw`.
+/// In particular, `get_raw_mut` must not create references to any value other than the one at `key`,
+/// so backends whose lookups read neighbouring values must resolve every pointer up front in `raw`.
+/// `BitSetCollection`'s mutable iterators rely on this to yield several `&mut` values at once.
+pub unsafe trait CollectionMut<'a, K>: StableCollection<'a, K> {
+    /// Raw handle to the collection's storage, taken once per mutable iteration.
+    type Raw;
+
+    /// Fetch a mutable reference to the value at `key`.
+    fn get_mut(&mut self, key: &K) -> Option<&mut Self::Item>;
+
+    /// Store `value` at `key` and return a mutable reference to it, looking the key up only once.
+    ///
+    /// Returns `Err` with the value if the collection is unable to store it.
+    fn insert_mut(&mut self, key: K, value: Self::Item) -> Result<&mut Self::Item, Self::Item>;
+
+    /// Take a raw handle to the collection's storage.
+    ///
+    /// This is free for the slice-backed collections, while `BTreeMap` and `HashMap` collect and sort
+    /// a pointer to every value they hold, costing an allocation and `O(n log n)` time per call.
+    fn raw(&mut self) -> Self::Raw;
+
+    /// Fetch a pointer to the value at `key` through a raw handle.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have been produced by `CollectionMut::raw` on a collection that is still mutably borrowed
+    /// and has not been accessed by any other means since, and `key` must be present in it.
+    unsafe fn get_raw_mut(raw: &Self::Raw, key: &K) -> *mut Self::Item;
+}
+
+unsafe impl<'a, V> CollectionMut<'a, usize> for Vec<V>
+where
+    V: Default,
+    Vec<V>: Collection<'a, usize, Item = V>,
+{
+    type Raw = *mut [V];
+
+    fn get_mut(&mut self, key: &usize) -> Option<&mut V> {
+        self.as_mut_slice().get_mut(*key)
+    }
+
+    fn insert_mut(&mut self, key: usize, value: V) -> Result<&mut V, V> {
+        if key >= self.len() {
+            self.resize_with(key + 1, Default::default);
+        }
+        let slot = &mut self.as_mut_slice()[key];
+        *slot = value;
+        Ok(slot)
+    }
+
+    fn raw(&mut self) -> Self::Raw {
+        self.as_mut_slice()
+    }
+
+    unsafe fn get_raw_mut(raw: &Self::Raw, key: &usize) -> *mut V {
+        assert!(*key < raw.len(), "Key {} out of bounds", key);
+        raw.cast::<V>().add(*key)
+    }
+}
+
+unsafe impl<'a, 'b, V> CollectionMut<'a, usize> for &'b mut [V]
+where
+    V: Default,
+    &'b mut [V]: Collection<'a, usize, Item = V>,
+{
+    type Raw = *mut [V];
+
+    fn get_mut(&mut self, key: &usize) -> Option<&mut V> {
+        <[V]>::get_mut(self, *key)
+    }
+
+    fn insert_mut(&mut self, key: usize, value: V) -> Result<&mut V, V> {
+        match <[V]>::get_mut(self, key) {
+            Some(slot) => {
+                *slot = value;
+                Ok(slot)
+            }
+            None => Err(value),
+        }
+    }
+
+    fn raw(&mut self) -> Self::Raw {
+        &mut **self
+    }
+
+    unsafe fn get_raw_mut(raw: &Self::Raw, key: &usize) -> *mut V {
+        assert!(*key < raw.len(), "Key {} out of bounds", key);
+        raw.cast::<V>().add(*key)
+    }
+}
+
+unsafe impl<'a, V> CollectionMut<'a, usize> for VecDeque<V>
+where
+    V: Default,
+    VecDeque<V>: Collection<'a, usize, Item = V>,
+{
+    type Raw = (*mut [V], *mut [V]);
+
+    fn get_mut(&mut self, key: &usize) -> Option<&mut V> {
+        VecDeque::get_mut(self, *key)
+    }
+
+    fn insert_mut(&mut self, key: usize, value: V) -> Result<&mut V, V> {
+        if key >= self.len() {
+            self.resize_with(key + 1, Default::default);
+        }
+        let slot = &mut self[key];
+        *slot = value;
+        Ok(slot)
+    }
+
+    fn raw(&mut self) -> Self::Raw {
+        let (front, back) = self.as_mut_slices();
+        (front, back)
+    }
+
+    unsafe fn get_raw_mut((front, back): &Self::Raw, key: &usize) -> *mut V {
+        if *key < front.len() {
+            front.cast::<V>().add(*key)
+        } else {
+            let key = *key - front.len();
+            assert!(key < back.len(), "Key {} out of bounds", key);
+            back.cast::<V>().add(key)
+        }
+    }
+}
+
+/// Resolve a pointer to every value of a map from a single mutable iteration, sorted by bitset index.
+///
+/// Looking values up through the map itself would read the nodes or buckets holding neighbouring values,
+/// invalidating `&mut` references already handed out for them.
+/// The table covers the whole map however few keys are then fetched from it.
+fn raw_entries<'b, K, V>(entries: impl Iterator<Item = (&'b K, &'b mut V)>) -> Vec<(u32, *mut V)>
+where
+    K: BitSetKey + 'b,
+    V: 'b,
+{
+    let mut entries = entries
+        .filter_map(|(key, value)| Some((key.to_index()?, value as *mut V)))
+        .collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|(index, _)| *index);
+    entries
+}
+
+/// Find the pointer resolved by `raw_entries` for `key`.
+fn raw_entry<K, V>(entries: &[(u32, *mut V)], key: &K) -> *mut V
+where
+    K: BitSetKey,
+{
+    key.to_index()
+        .and_then(|index| {
+            entries
+                .binary_search_by_key(&index, |(index, _)| *index)
+                .ok()
+        })
+        .map(|position| entries[position].1)
+        .expect("Key not present in map")
+}
+
+unsafe impl<'a, K, V> CollectionMut<'a, K> for BTreeMap<K, V>
+where
+    K: Ord + BitSetKey,
+    BTreeMap<K, V>: Collection<'a, K, Item = V>,
+{
+    type Raw = Vec<(u32, *mut V)>;
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        BTreeMap::get_mut(self, key)
+    }
+
+    fn insert_mut(&mut self, key: K, value: V) -> Result<&mut V, V> {
+        match BTreeMap::entry(self, key) {
+            btree_map::Entry::Vacant(entry) => Ok(entry.insert(value)),
+            btree_map::Entry::Occupied(entry) => {
+                let slot = entry.into_mut();
+                *slot = value;
+                Ok(slot)
+            }
+        }
+    }
+
+    fn raw(&mut self) -> Self::Raw {
+        raw_entries(BTreeMap::iter_mut(self))
+    }
+
+    unsafe fn get_raw_mut(raw: &Self::Raw, key: &K) -> *mut V {
+        raw_entry(raw, key)
+    }
+}
+
+unsafe impl<'a, K, V> CollectionMut<'a, K> for HashMap<K, V>
+where
+    K: Eq + Hash + BitSetKey,
+    HashMap<K, V>: Collection<'a, K, Item = V>,
+{
+    type Raw = Vec<(u32, *mut V)>;
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        HashMap::get_mut(self, key)
+    }
+
+    fn insert_mut(&mut self, key: K, value: V) -> Result<&mut V, V> {
+        match HashMap::entry(self, key) {
+            hash_map::Entry::Vacant(entry) => Ok(entry.insert(value)),
+            hash_map::Entry::Occupied(entry) => {
+                let slot = entry.into_mut();
+                *slot = value;
+                Ok(slot)
+            }
+        }
+    }
+
+    fn raw(&mut self) -> Self::Raw {
+        raw_entries(HashMap::iter_mut(self))
+    }
+
+    unsafe fn get_raw_mut(raw: &Self::Raw, key: &K) -> *mut V {
+        raw_entry(raw, key)
+    }
+}
+
+/// Marker for `CollectionMut` implementors whose `get_raw_mut` may be called concurrently from several threads,
+/// provided each thread accesses distinct keys.
+///
+/// # Safety
+///
+/// `get_raw_mut` must not create references to the collection itself or to any value other than the one at `key`.
+pub unsafe trait DisjointCollectionMut<'a, K>: CollectionMut<'a, K> {
+    /// Check whether a raw handle has a slot for `key`.
+    fn raw_contains_key(raw: &Self::Raw, key: &K) -> bool;
+}
+
+unsafe impl<'a, V> DisjointCollectionMut<'a, usize> for Vec<V>
+where
+    V: Default,
+    Vec<V>: Collection<'a, usize, Item = V>,
+{
+    fn raw_contains_key(raw: &Self::Raw, key: &usize) -> bool {
+        *key < raw.len()
+    }
+}
+
+unsafe impl<'a, 'b, V> DisjointCollectionMut<'a, usize> for &'b mut [V]
+where
+    V: Default,
+    &'b mut [V]: Collection<'a, usize, Item = V>,
+{
+    fn raw_contains_key(raw: &Self::Raw, key: &usize) -> bool {
+        *key < raw.len()
+    }
+}
+
+unsafe impl<'a, V> DisjointCollectionMut<'a, usize> for VecDeque<V>
+where
+    V: Default,
+    VecDeque<V>: Collection<'a, usize, Item = V>,
+{
+    fn raw_contains_key((front, back): &Self::Raw, key: &usize) -> bool {
+        *key < front.len() + back.len()
+    }
+}
@@ -0,0 +1,502 @@
+use core::marker::PhantomData;
+
+use hibitset::{BitIter, BitSet, BitSetLike};
+
+use collection_trait::Collection;
+
+use crate::{
+    key_index, BitSetCollectionError, BitSetKey, BitSetMask, CollectionMut, Join, JoinIter,
+    Joinable, StableCollection,
+};
+
+/// Tuple of `StableCollection`s holding one column each of a `BitSetColumns`' rows.
+pub trait Columns<'a, K> {
+    /// One value per column, in column order.
+    type Row;
+
+    /// Store `row` at `key` across every column, returning the previous row if every column held a value.
+    ///
+    /// If a column rejects its value, the columns before it are restored and the row is returned as `Err`.
+    fn insert_row(&mut self, key: K, row: Self::Row) -> Result<Option<Self::Row>, Self::Row>;
+
+    /// Take the values stored at `key` from every column.
+    fn remove_row(&mut self, key: &K) -> Option<Self::Row>;
+
+    /// Drop every stored value in every column.
+    fn clear_rows(&mut self);
+}
+
+/// Put `previous` back into `column` at `key`, returning the value it displaces.
+fn restore<'a, K, C>(column: &mut C, key: K, previous: Option<C::Item>) -> C::Item
+where
+    C: StableCollection<'a, K>,
+{
+    let displaced = match previous {
+        Some(previous) => column.stable_insert(key, previous).ok().flatten(),
+        None => column.stable_remove(&key),
+    };
+    displaced.expect("Column rejected a value at a key it had just accepted")
+}
+
+/// Insert each value into its column in turn, restoring the columns already written if one rejects its value.
+macro_rules! insert_columns {
+    (
+        $key:ident;
+        [$(($dc:ident, $dv:ident, $dp:ident))*];
+        ($c:ident, $v:ident, $p:ident) $(, ($rc:ident, $rv:ident, $rp:ident))*
+    ) => {{
+        let $p = match $c.stable_insert($key, $v) {
+            Ok(previous) => previous,
+            Err($v) => {
+                $(let $dv = restore($dc, $key, $dp);)*
+                return Err(($($dv,)* $v, $($rv,)*));
+            }
+        };
+        insert_columns!($key; [$(($dc, $dv, $dp))* ($c, $v, $p)]; $(($rc, $rv, $rp)),*)
+    }};
+    ($key:ident; [$(($dc:ident, $dv:ident, $dp:ident))*];) => {
+        match ($($dp,)*) {
+            ($(Some($dp),)*) => Ok(Some(($($dp,)*))),
+            _ => Ok(None),
+        }
+    };
+}
+
+macro_rules! impl_columns {
+    ($(($t:ident, $v:ident, $p:ident)),+) => {
+        impl<'a, K, $($t),+> Columns<'a, K> for ($($t,)+)
+        where
+            K: Copy,
+            $($t: StableCollection<'a, K>,)+
+        {
+            type Row = ($($t::Item,)+);
+
+            #[allow(non_snake_case)]
+            fn insert_row(&mut self, key: K, row: Self::Row) -> Result<Option<Self::Row>, Self::Row> {
+                let ($($t,)+) = self;
+                let ($($v,)+) = row;
+                insert_columns!(key; []; $(($t, $v, $p)),+)
+            }
+
+            #[allow(non_snake_case)]
+            fn remove_row(&mut self, key: &K) -> Option<Self::Row> {
+                let ($($t,)+) = self;
+                let ($($v,)+) = ($($t.stable_remove(key),)+);
+                Some(($($v?,)+))
+            }
+
+            #[allow(non_snake_case)]
+            fn clear_rows(&mut self) {
+                let ($($t,)+) = self;
+                $($t.stable_clear();)+
+            }
+        }
+
+        impl<'a, K, M, $($t),+> BitSetColumns<'a, K, ($($t,)+), M>
+        where
+            $($t: Collection<'a, K>,)+
+        {
+            /// Borrow every column for reading.
+            #[allow(non_snake_case)]
+            pub fn columns(&'a self) -> ($(Column<'a, K, $t, M>,)+) {
+                let ($($t,)+) = &self.columns;
+                ($(Column::new(&self.bitset, $t),)+)
+            }
+
+            /// Borrow every column for writing.
+            ///
+            /// Rows can only be inserted or removed through the `BitSetColumns` itself,
+            /// so the columns always agree on which keys are present.
+            #[allow(non_snake_case)]
+            pub fn columns_mut(&mut self) -> ($(ColumnMut<'a, '_, K, $t, M>,)+) {
+                let ($($t,)+) = &mut self.columns;
+                ($(ColumnMut::new(&self.bitset, $t),)+)
+            }
+        }
+    };
+}
+
+impl_columns!((A, a, previous_a));
+impl_columns!((A, a, previous_a), (B, b, previous_b));
+impl_columns!((A, a, previous_a), (B, b, previous_b), (C, c, previous_c));
+impl_columns!(
+    (A, a, previous_a),
+    (B, b, previous_b),
+    (C, c, previous_c),
+    (D, d, previous_d)
+);
+impl_columns!(
+    (A, a, previous_a),
+    (B, b, previous_b),
+    (C, c, previous_c),
+    (D, d, previous_d),
+    (E, e, previous_e)
+);
+impl_columns!(
+    (A, a, previous_a),
+    (B, b, previous_b),
+    (C, c, previous_c),
+    (D, d, previous_d),
+    (E, e, previous_e),
+    (F, f, previous_f)
+);
+impl_columns!(
+    (A, a, previous_a),
+    (B, b, previous_b),
+    (C, c, previous_c),
+    (D, d, previous_d),
+    (E, e, previous_e),
+    (F, f, previous_f),
+    (G, g, previous_g)
+);
+impl_columns!(
+    (A, a, previous_a),
+    (B, b, previous_b),
+    (C, c, previous_c),
+    (D, d, previous_d),
+    (E, e, previous_e),
+    (F, f, previous_f),
+    (G, g, previous_g),
+    (H, h, previous_h)
+);
+
+/// Struct-of-arrays collection whose columns share a single bitset of present keys.
+///
+/// `C` is a tuple of `StableCollection`s, one per column.
+/// Rows are inserted and removed whole, so every column holds a value for each present key,
+/// while `columns` and `columns_mut` split the collection into per-column views for iteration and joins.
+#[derive(Debug, Clone)]
+pub struct BitSetColumns<'a, K, C, M = BitSet> {
+    bitset: M,
+    columns: C,
+    len: usize,
+    _phantom: PhantomData<&'a K>,
+}
+
+impl<'a, K, C, M> Default for BitSetColumns<'a, K, C, M>
+where
+    C: Default,
+    M: Default,
+{
+    fn default() -> Self {
+        BitSetColumns::with_columns(C::default())
+    }
+}
+
+impl<'a, K, C, M> BitSetColumns<'a, K, C, M> {
+    /// Wrap `columns` without marking any of their keys as present.
+    pub fn with_columns(columns: C) -> Self
+    where
+        M: Default,
+    {
+        BitSetColumns {
+            bitset: M::default(),
+            columns,
+            len: 0,
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Number of rows present in the bitset.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the keys of present rows in ascending order.
+    pub fn keys(&self) -> core::iter::Map<BitIter<&M>, fn(u32) -> K>
+    where
+        K: BitSetKey,
+        M: BitSetLike,
+    {
+        (&self.bitset).iter().map(K::from_index as fn(u32) -> K)
+    }
+}
+
+impl<'a, K, C, M> BitSetColumns<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Columns<'a, K>,
+    M: BitSetMask,
+{
+    /// Check whether a row is present at `key`.
+    pub fn try_contains_key(&self, key: &K) -> Result<bool, BitSetCollectionError> {
+        Ok(self.bitset.contains(key_index(*key)?))
+    }
+
+    /// Check whether a row is present at `key`.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.try_contains_key(key).unwrap()
+    }
+
+    /// Insert `row` at `key`, returning the previous row if the key was present.
+    ///
+    /// Fails without changing any column if one of them is unable to store its value.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        row: C::Row,
+    ) -> Result<Option<C::Row>, BitSetCollectionError> {
+        let index = key_index(key)?;
+        let previous = self
+            .columns
+            .insert_row(key, row)
+            .map_err(|_| BitSetCollectionError::InsertRejected(index))?;
+
+        if self.bitset.add(index) {
+            Ok(previous)
+        } else {
+            self.len += 1;
+            Ok(None)
+        }
+    }
+
+    /// Insert `row` at `key`, returning the previous row if the key was present.
+    ///
+    /// Panics under the same conditions as `try_insert` fails.
+    pub fn insert(&mut self, key: K, row: C::Row) -> Option<C::Row> {
+        self.try_insert(key, row).unwrap()
+    }
+
+    /// Remove and return the row at `key`, leaving the rows at other keys in place.
+    pub fn try_remove(&mut self, key: &K) -> Result<Option<C::Row>, BitSetCollectionError> {
+        if self.bitset.remove(key_index(*key)?) {
+            self.len -= 1;
+            Ok(self.columns.remove_row(key))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Remove and return the row at `key`, leaving the rows at other keys in place.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn remove(&mut self, key: &K) -> Option<C::Row> {
+        self.try_remove(key).unwrap()
+    }
+
+    /// Remove every row, resetting both the bitset and every column.
+    pub fn clear(&mut self) {
+        self.bitset.clear();
+        self.len = 0;
+        self.columns.clear_rows();
+    }
+}
+
+/// Read-only view of one column of a `BitSetColumns`.
+///
+/// Also a `Joinable`, so that columns can be joined with each other or with other collections.
+#[derive(Debug)]
+pub struct Column<'a, K, C, M = BitSet> {
+    bitset: &'a M,
+    column: &'a C,
+    _phantom: PhantomData<K>,
+}
+
+// Implemented by hand so that the view is `Copy` whatever its type parameters.
+impl<'a, K, C, M> Clone for Column<'a, K, C, M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, K, C, M> Copy for Column<'a, K, C, M> {}
+
+impl<'a, K, C, M> Column<'a, K, C, M> {
+    fn new(bitset: &'a M, column: &'a C) -> Self {
+        Column {
+            bitset,
+            column,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, C, M> Column<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    /// Fetch this column's value at `key`.
+    pub fn try_get(&self, key: &K) -> Result<Option<&'a C::Item>, BitSetCollectionError> {
+        if self.bitset.contains(key_index(*key)?) {
+            Ok(Some(self.column.get_unchecked(key)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Fetch this column's value at `key`.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get(&self, key: &K) -> Option<&'a C::Item> {
+        self.try_get(key).unwrap()
+    }
+
+    /// Iterate over present keys and this column's values in ascending key order.
+    pub fn iter(self) -> JoinIter<(Self,)> {
+        (self,).join()
+    }
+}
+
+impl<'a, K, C, M> Joinable for Column<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    type Key = K;
+    type Mask = &'a M;
+    type Value = &'a C;
+    type Item = &'a C::Item;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (self.bitset, self.column)
+    }
+
+    fn mask(self) -> Self::Mask {
+        self.bitset
+    }
+
+    unsafe fn fetch(value: &Self::Value, _: u32, key: K) -> Self::Item {
+        value.get_unchecked(&key)
+    }
+}
+
+/// Mutable view of one column of a `BitSetColumns`.
+///
+/// Values can be modified in place, but rows can only be inserted or removed through the `BitSetColumns`.
+#[derive(Debug)]
+pub struct ColumnMut<'a, 'b, K, C, M = BitSet> {
+    bitset: &'b M,
+    column: &'b mut C,
+    _phantom: PhantomData<&'a K>,
+}
+
+impl<'a, 'b, K, C, M> ColumnMut<'a, 'b, K, C, M> {
+    fn new(bitset: &'b M, column: &'b mut C) -> Self {
+        ColumnMut {
+            bitset,
+            column,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'b, K, C, M> ColumnMut<'a, 'b, K, C, M>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    M: BitSetLike,
+{
+    /// Fetch a mutable reference to this column's value at `key`.
+    pub fn try_get_mut(&mut self, key: &K) -> Result<Option<&mut C::Item>, BitSetCollectionError> {
+        if self.bitset.contains(key_index(*key)?) {
+            Ok(CollectionMut::get_mut(&mut *self.column, key))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Fetch a mutable reference to this column's value at `key`.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut C::Item> {
+        self.try_get_mut(key).unwrap()
+    }
+
+    /// Iterate over present keys and mutable references to this column's values in ascending key order.
+    pub fn iter_mut(self) -> JoinIter<(Self,)>
+    where
+        C::Item: 'b,
+    {
+        (self,).join()
+    }
+}
+
+impl<'a, 'b, K, C, M> Joinable for ColumnMut<'a, 'b, K, C, M>
+where
+    C: CollectionMut<'a, K>,
+    C::Item: 'b,
+    M: BitSetLike,
+{
+    type Key = K;
+    type Mask = &'b M;
+    type Value = C::Raw;
+    type Item = &'b mut C::Item;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (self.bitset, self.column.raw())
+    }
+
+    fn mask(self) -> Self::Mask {
+        self.bitset
+    }
+
+    unsafe fn fetch(value: &Self::Value, _: u32, key: K) -> Self::Item {
+        &mut *C::get_raw_mut(value, &key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BitSetCollectionError, BitSetColumns, Join};
+
+    type Bodies<'a> = BitSetColumns<'a, usize, (Vec<f32>, Vec<f32>, Vec<u8>)>;
+
+    #[test]
+    fn rows_share_one_bitset() {
+        let mut bodies = Bodies::default();
+        assert_eq!(bodies.insert(0, (0.0, 1.0, 1)), None);
+        assert_eq!(bodies.insert(3, (3.0, -1.0, 2)), None);
+        assert_eq!(bodies.insert(5, (5.0, 0.5, 3)), None);
+        assert_eq!(bodies.insert(3, (30.0, -1.0, 2)), Some((3.0, -1.0, 2)));
+        assert_eq!(bodies.remove(&0), Some((0.0, 1.0, 1)));
+        assert_eq!(bodies.remove(&0), None);
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies.keys().collect::<Vec<_>>(), vec![3, 5]);
+
+        let (mut positions, velocities, _) = bodies.columns_mut();
+        assert_eq!(positions.get_mut(&0), None);
+        for (_, position, velocity) in (positions, velocities).join() {
+            *position += *velocity;
+        }
+
+        let (positions, _, masses) = bodies.columns();
+        assert_eq!(
+            positions.iter().collect::<Vec<_>>(),
+            vec![(3, &29.0), (5, &5.5)]
+        );
+        assert_eq!(masses.get(&5), Some(&3));
+        assert_eq!(masses.get(&0), None);
+    }
+
+    #[test]
+    fn rejected_row_restores_columns() {
+        let mut masses = [0u8; 2];
+        let mut bodies =
+            BitSetColumns::<usize, (Vec<f32>, &mut [u8])>::with_columns((vec![], &mut masses[..]));
+        bodies.insert(1, (1.0, 1));
+
+        assert_eq!(bodies.try_insert(1, (10.0, 10)), Ok(Some((1.0, 1))));
+        assert_eq!(
+            bodies.try_insert(4, (4.0, 4)),
+            Err(BitSetCollectionError::InsertRejected(4))
+        );
+        assert!(!bodies.contains_key(&4));
+
+        let (positions, masses) = bodies.columns_mut();
+        assert_eq!(
+            (positions, masses).join().collect::<Vec<_>>(),
+            vec![(1, &mut 10.0, &mut 10)]
+        );
+
+        bodies.clear();
+        assert!(bodies.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use hibitset::{AtomicBitSet, BitSet, BitSetLike};
+
+use crate::{
+    key_index, BitSetCollection, BitSetCollectionError, BitSetKey, BitSetMask,
+    DisjointCollectionMut,
+};
+
+/// View of a `BitSetCollection` that allows several threads to insert into
+/// disjoint, preallocated slots of its collection through `&self`.
+///
+/// Inserted keys are recorded in an `AtomicBitSet` and only merged into the collection's bitset by `maintain`,
+/// or when the view is dropped. Leaking the view with `mem::forget` leaves every key inserted since the last
+/// `maintain` absent, though its value stays in the collection's slot.
+pub struct BitSetCollectionConcurrent<'a, 'b, K, C, M = BitSet>
+where
+    C: DisjointCollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    collection: &'b mut BitSetCollection<'a, K, C, M>,
+    raw: C::Raw,
+    pending: AtomicBitSet,
+}
+
+unsafe impl<'a, 'b, K, C, M> Send for BitSetCollectionConcurrent<'a, 'b, K, C, M>
+where
+    C: DisjointCollectionMut<'a, K> + Send,
+    C::Item: Send,
+    M: BitSetMask + Send,
+{
+}
+
+// Safety: concurrent inserts only touch the slot of the index they claimed in `pending`,
+// and otherwise read the collection's bitset.
+unsafe impl<'a, 'b, K, C, M> Sync for BitSetCollectionConcurrent<'a, 'b, K, C, M>
+where
+    C: DisjointCollectionMut<'a, K>,
+    C::Item: Send,
+    M: BitSetMask + Sync,
+{
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    C: DisjointCollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Borrow the collection for concurrent insertion.
+    ///
+    /// The collection must already hold a slot for every key inserted through the returned view.
+    pub fn concurrent(&mut self) -> BitSetCollectionConcurrent<'a, '_, K, C, M> {
+        BitSetCollectionConcurrent {
+            raw: self.collection.raw(),
+            collection: self,
+            pending: AtomicBitSet::new(),
+        }
+    }
+}
+
+impl<'a, 'b, K, C, M> BitSetCollectionConcurrent<'a, 'b, K, C, M>
+where
+    K: BitSetKey,
+    C: DisjointCollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Store `value` at `key`, returning the previous value if the key was present before this view was created.
+    ///
+    /// Fails if the collection has no slot for `key`, or if `key` was already inserted through this view.
+    pub fn insert(&self, key: K, value: C::Item) -> Result<Option<C::Item>, BitSetCollectionError> {
+        let index = key_index(key)?;
+
+        if !C::raw_contains_key(&self.raw, &key) {
+            return Err(BitSetCollectionError::InsertRejected(index));
+        }
+
+        if self.pending.add_atomic(index) {
+            return Err(BitSetCollectionError::IndexClaimed(index));
+        }
+
+        // Safety: the slot exists, and claiming `index` in `pending` grants this call exclusive access to it.
+        let previous = unsafe { core::mem::replace(&mut *C::get_raw_mut(&self.raw, &key), value) };
+
+        if self.collection.bitset.contains(index) {
+            Ok(Some(previous))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a, 'b, K, C, M> BitSetCollectionConcurrent<'a, 'b, K, C, M>
+where
+    C: DisjointCollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Merge the keys inserted through this view into the collection's bitset.
+    ///
+    /// Acts as a sync point: afterwards the view can be used again, and may insert at the merged keys once more.
+    pub fn maintain(&mut self) {
+        for index in (&self.pending).iter() {
+            self.collection.add_index(index);
+        }
+        self.pending.clear();
+    }
+}
+
+impl<'a, 'b, K, C, M> Drop for BitSetCollectionConcurrent<'a, 'b, K, C, M>
+where
+    C: DisjointCollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    fn drop(&mut self) {
+        self.maintain();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use collection_trait::Collection;
+
+    use crate::{BitSetCollectionError, BitSetVec};
+
+    #[test]
+    fn bitset_vec_concurrent_insert() {
+        let mut collection = BitSetVec::<usize, usize>::with_collection(vec![0; 64]);
+        collection.insert(3, 100);
+
+        let mut concurrent = collection.concurrent();
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let concurrent = &concurrent;
+                scope.spawn(move || {
+                    for key in (thread..64).step_by(4) {
+                        let previous = concurrent.insert(key, key).unwrap();
+                        assert_eq!(previous, if key == 3 { Some(100) } else { None });
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            concurrent.insert(5, 5),
+            Err(BitSetCollectionError::IndexClaimed(5))
+        );
+        assert_eq!(
+            concurrent.insert(64, 64),
+            Err(BitSetCollectionError::InsertRejected(64))
+        );
+        concurrent.maintain();
+
+        assert_eq!(concurrent.insert(5, 5), Ok(Some(5)));
+        drop(concurrent);
+
+        assert_eq!(collection.len(), 64);
+        assert!(collection.iter().all(|(key, value)| key == *value));
+    }
+}
@@ -0,0 +1,269 @@
+use hibitset::BitSet;
+
+use crate::{
+    key_index, BitSetCollection, BitSetCollectionError, BitSetKey, BitSetMask, CollectionMut,
+};
+
+/// View into a single key of a `BitSetCollection`, which may be present or absent.
+pub enum Entry<'a, 'b, K, C, M = BitSet>
+where
+    C: CollectionMut<'a, K>,
+{
+    Occupied(OccupiedEntry<'a, 'b, K, C, M>),
+    Vacant(VacantEntry<'a, 'b, K, C, M>),
+}
+
+/// View into a key present in a `BitSetCollection`.
+pub struct OccupiedEntry<'a, 'b, K, C, M = BitSet>
+where
+    C: CollectionMut<'a, K>,
+{
+    collection: &'b mut BitSetCollection<'a, K, C, M>,
+    key: K,
+    index: u32,
+}
+
+/// View into a key absent from a `BitSetCollection`.
+pub struct VacantEntry<'a, 'b, K, C, M = BitSet>
+where
+    C: CollectionMut<'a, K>,
+{
+    collection: &'b mut BitSetCollection<'a, K, C, M>,
+    key: K,
+    index: u32,
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Get the entry for `key`, converting it into a bitset index only once.
+    pub fn try_entry(&mut self, key: K) -> Result<Entry<'a, '_, K, C, M>, BitSetCollectionError> {
+        let index = key_index(key)?;
+        let entry = if self.bitset.contains(index) {
+            Entry::Occupied(OccupiedEntry {
+                collection: self,
+                key,
+                index,
+            })
+        } else {
+            Entry::Vacant(VacantEntry {
+                collection: self,
+                key,
+                index,
+            })
+        };
+        Ok(entry)
+    }
+
+    /// Get the entry for `key`.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn entry(&mut self, key: K) -> Entry<'a, '_, K, C, M> {
+        self.try_entry(key).unwrap()
+    }
+}
+
+impl<'a, 'b, K, C, M> Entry<'a, 'b, K, C, M>
+where
+    K: Copy,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Insert `default` if the key is absent, and return a mutable reference to its value.
+    pub fn try_or_insert(self, default: C::Item) -> Result<&'b mut C::Item, BitSetCollectionError> {
+        self.try_or_insert_with(|| default)
+    }
+
+    /// Insert `default` if the key is absent, and return a mutable reference to its value.
+    ///
+    /// Panics under the same conditions as `try_or_insert` fails.
+    pub fn or_insert(self, default: C::Item) -> &'b mut C::Item {
+        self.try_or_insert(default).unwrap()
+    }
+
+    /// Insert the result of `default` if the key is absent, and return a mutable reference to its value.
+    pub fn try_or_insert_with<F>(self, default: F) -> Result<&'b mut C::Item, BitSetCollectionError>
+    where
+        F: FnOnce() -> C::Item,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.try_insert(default()),
+        }
+    }
+
+    /// Insert the result of `default` if the key is absent, and return a mutable reference to its value.
+    ///
+    /// Panics under the same conditions as `try_or_insert_with` fails.
+    pub fn or_insert_with<F>(self, default: F) -> &'b mut C::Item
+    where
+        F: FnOnce() -> C::Item,
+    {
+        self.try_or_insert_with(default).unwrap()
+    }
+
+    /// Insert the default value if the key is absent, and return a mutable reference to its value.
+    pub fn try_or_default(self) -> Result<&'b mut C::Item, BitSetCollectionError>
+    where
+        C::Item: Default,
+    {
+        self.try_or_insert_with(Default::default)
+    }
+
+    /// Insert the default value if the key is absent, and return a mutable reference to its value.
+    ///
+    /// Panics under the same conditions as `try_or_default` fails.
+    pub fn or_default(self) -> &'b mut C::Item
+    where
+        C::Item: Default,
+    {
+        self.try_or_default().unwrap()
+    }
+
+    /// Modify the value in place if the key is present.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut C::Item),
+    {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, 'b, K, C, M> OccupiedEntry<'a, 'b, K, C, M>
+where
+    K: Copy,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn get_mut(&mut self) -> &mut C::Item {
+        self.collection
+            .collection
+            .get_mut(&self.key)
+            .expect("Key present in bitset but not in collection")
+    }
+
+    /// Convert the entry into a mutable reference to its value, bound to the collection's borrow.
+    pub fn into_mut(self) -> &'b mut C::Item {
+        self.collection
+            .collection
+            .get_mut(&self.key)
+            .expect("Key present in bitset but not in collection")
+    }
+
+    /// Replace the entry's value, returning the previous one.
+    pub fn insert(&mut self, value: C::Item) -> C::Item {
+        core::mem::replace(self.get_mut(), value)
+    }
+
+    /// Remove the entry from the collection, returning its value.
+    pub fn remove(self) -> Option<C::Item> {
+        self.collection.remove_index(self.index);
+        self.collection.collection.stable_remove(&self.key)
+    }
+}
+
+impl<'a, 'b, K, C, M> VacantEntry<'a, 'b, K, C, M>
+where
+    K: Copy,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Insert `value` at the entry's key, returning a mutable reference to it.
+    ///
+    /// Fails if the wrapped collection is unable to store the value, leaving the key absent.
+    pub fn try_insert(self, value: C::Item) -> Result<&'b mut C::Item, BitSetCollectionError> {
+        let VacantEntry {
+            collection,
+            key,
+            index,
+        } = self;
+
+        let slot = collection
+            .collection
+            .insert_mut(key, value)
+            .map_err(|_| BitSetCollectionError::InsertRejected(index))?;
+
+        // The slot keeps the wrapped collection borrowed, so the bitset is updated field by field.
+        if !collection.bitset.add(index) {
+            collection.len += 1;
+        }
+        Ok(slot)
+    }
+
+    /// Insert `value` at the entry's key, returning a mutable reference to it.
+    ///
+    /// Panics under the same conditions as `try_insert` fails.
+    pub fn insert(self, value: C::Item) -> &'b mut C::Item {
+        self.try_insert(value).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use collection_trait::Collection;
+
+    use crate::{BitSetBTreeMap, BitSetCollectionError, BitSetMutSlice, BitSetVec, Entry};
+
+    #[test]
+    fn bitset_btree_map_entry_counter() {
+        let mut counters = BitSetBTreeMap::<usize, usize>::default();
+        for key in [1, 3, 1, 1, 3, 7].iter().copied() {
+            *counters.entry(key).or_insert(0) += 1;
+        }
+        assert_eq!(
+            counters.iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>(),
+            vec![(1, 3), (3, 2), (7, 1)]
+        );
+    }
+
+    #[test]
+    fn bitset_vec_entry_and_modify() {
+        let mut collection = BitSetVec::<usize, usize>::default();
+        collection.entry(2).and_modify(|v| *v += 1).or_default();
+        collection.entry(2).and_modify(|v| *v += 1).or_default();
+        assert_eq!(collection.get(&2), Some(&1));
+        assert!(!collection.contains_key(&1));
+
+        match collection.entry(2) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), Some(1)),
+            Entry::Vacant(_) => panic!("Expected occupied entry"),
+        }
+        assert!(!collection.contains_key(&2));
+    }
+
+    #[test]
+    fn bitset_mut_slice_entry_rejected() {
+        let mut values = [0usize; 4];
+        let mut collection = BitSetMutSlice::<usize, usize>::with_collection(&mut values[..]);
+
+        *collection.entry(3).or_insert(1) += 1;
+        assert_eq!(collection.get(&3), Some(&2));
+        assert_eq!(
+            collection.entry(5).try_or_insert(1),
+            Err(BitSetCollectionError::InsertRejected(5))
+        );
+        assert!(!collection.contains_key(&5));
+        assert_eq!(collection.len(), 1);
+    }
+}
@@ -0,0 +1,47 @@
+use core::fmt::{Display, Formatter};
+
+/// Error produced by the fallible `try_*` family of `BitSetCollection` methods.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BitSetCollectionError {
+    /// The key could not be converted into a `u32` bitset index.
+    KeyOutOfRange,
+    /// The key converted into an index that the underlying `BitSet` is unable to hold.
+    KeyBeyondCapacity(u32),
+    /// The wrapped collection did not store the value inserted at this index.
+    InsertRejected(u32),
+    /// Another concurrent insert already claimed this index.
+    IndexClaimed(u32),
+    /// The key's generation is older than the one stored at this index.
+    StaleGeneration(u32),
+}
+
+impl Display for BitSetCollectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BitSetCollectionError::KeyOutOfRange => {
+                write!(f, "key cannot be represented as a bitset index")
+            }
+            BitSetCollectionError::KeyBeyondCapacity(index) => write!(
+                f,
+                "index {} exceeds bitset capacity of {}",
+                index,
+                crate::BITSET_CAPACITY
+            ),
+            BitSetCollectionError::InsertRejected(index) => {
+                write!(f, "collection rejected insert at index {}", index)
+            }
+            BitSetCollectionError::IndexClaimed(index) => {
+                write!(
+                    f,
+                    "index {} was already claimed by a concurrent insert",
+                    index
+                )
+            }
+            BitSetCollectionError::StaleGeneration(index) => {
+                write!(f, "key generation is stale for index {}", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BitSetCollectionError {}
@@ -0,0 +1,346 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use hibitset::BitSet;
+
+use collection_trait::Collection;
+
+use crate::{
+    key_index, BitSetCollection, BitSetCollectionError, BitSetKey, BitSetMask, CollectionMut,
+    StableCollection,
+};
+
+/// Change to an `Observed` collection, in the order it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent<K, V> {
+    /// The key was absent and now holds a value.
+    Inserted(K),
+    /// The key's value was replaced or mutably borrowed.
+    Modified(K),
+    /// The key was removed, taking the contained value with it.
+    Removed(K, V),
+}
+
+/// Source of the ids distinguishing `Observed` collections, so readers cannot be used with the wrong one.
+static NEXT_OBSERVED_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Handle to a reader's position in an `Observed` collection's event stream.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct ReaderId {
+    /// Id of the `Observed` collection the reader was registered with.
+    owner: usize,
+    slot: usize,
+}
+
+/// Iterator over the events a reader has not yet seen.
+pub type ChangeEvents<'b, K, V> = alloc::collections::vec_deque::Iter<'b, ChangeEvent<K, V>>;
+
+/// `BitSetCollection` wrapper emitting a `ChangeEvent` for every insertion, modification and removal.
+///
+/// Each registered reader has its own cursor into the event stream,
+/// and events are retained until every reader has seen them.
+/// Events emitted while no readers are registered are discarded.
+#[derive(Debug)]
+pub struct Observed<'a, K, C, M = BitSet>
+where
+    C: Collection<'a, K>,
+{
+    collection: BitSetCollection<'a, K, C, M>,
+    /// Id stamped into every `ReaderId` this collection registers.
+    id: usize,
+    events: VecDeque<ChangeEvent<K, C::Item>>,
+    /// Stream position of the front of `events`.
+    offset: usize,
+    /// Stream position of each reader's next unread event, `None` for freed reader slots.
+    cursors: Vec<Option<usize>>,
+}
+
+impl<'a, K, C, M> Default for Observed<'a, K, C, M>
+where
+    C: Collection<'a, K> + Default,
+    M: Default,
+{
+    fn default() -> Self {
+        Observed::new(Default::default())
+    }
+}
+
+impl<'a, K, C, M> Deref for Observed<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+{
+    type Target = BitSetCollection<'a, K, C, M>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.collection
+    }
+}
+
+impl<'a, K, C, M> Observed<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+{
+    /// Emit events for changes to `collection` from this point on.
+    pub fn new(collection: BitSetCollection<'a, K, C, M>) -> Self {
+        Observed {
+            collection,
+            id: NEXT_OBSERVED_ID.fetch_add(1, Ordering::Relaxed),
+            events: VecDeque::new(),
+            offset: 0,
+            cursors: Vec::new(),
+        }
+    }
+
+    /// Stop emitting events and return the wrapped collection.
+    pub fn into_inner(self) -> BitSetCollection<'a, K, C, M> {
+        self.collection
+    }
+
+    /// Register a reader that will see every event emitted from now on.
+    pub fn register_reader(&mut self) -> ReaderId {
+        let cursor = Some(self.offset + self.events.len());
+        let slot = match self.cursors[..].iter().position(Option::is_none) {
+            Some(slot) => {
+                self.cursors[slot] = cursor;
+                slot
+            }
+            None => {
+                self.cursors.push(cursor);
+                self.cursors.len() - 1
+            }
+        };
+        ReaderId {
+            owner: self.id,
+            slot,
+        }
+    }
+
+    /// Unregister a reader, releasing any events only it had yet to see.
+    ///
+    /// Panics if `reader` was registered with a different collection.
+    pub fn remove_reader(&mut self, reader: ReaderId) {
+        *self.cursor_mut(&reader) = None;
+        self.trim();
+    }
+
+    /// Iterate over the events `reader` has not yet seen, marking them as seen.
+    ///
+    /// Panics if `reader` was registered with a different collection.
+    pub fn read(&mut self, reader: &ReaderId) -> ChangeEvents<'_, K, C::Item> {
+        self.trim();
+
+        let end = self.offset + self.events.len();
+        let cursor = self
+            .cursor_mut(reader)
+            .as_mut()
+            .expect("Reader is not registered with this collection");
+        let start = core::mem::replace(cursor, end) - self.offset;
+
+        self.events.range(start..)
+    }
+
+    /// Cursor slot of `reader`, checking that it belongs to this collection.
+    fn cursor_mut(&mut self, reader: &ReaderId) -> &mut Option<usize> {
+        assert_eq!(
+            reader.owner, self.id,
+            "Reader is not registered with this collection"
+        );
+        &mut self.cursors[reader.slot]
+    }
+
+    /// Drop events that every registered reader has already seen.
+    fn trim(&mut self) {
+        let seen = self.cursors[..]
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+            .unwrap_or(self.offset + self.events.len());
+        self.events.drain(..seen - self.offset);
+        self.offset = seen;
+    }
+
+    fn emit(&mut self, event: ChangeEvent<K, C::Item>) {
+        self.emit_with(|| event);
+    }
+
+    /// Emit the event built by `event`, only building it if a registered reader will see it.
+    fn emit_with<F>(&mut self, event: F)
+    where
+        F: FnOnce() -> ChangeEvent<K, C::Item>,
+    {
+        if self.cursors[..].iter().any(Option::is_some) {
+            self.events.push_back(event());
+        } else {
+            self.offset += 1;
+        }
+    }
+}
+
+impl<'a, K, C, M> Observed<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: StableCollection<'a, K>,
+    M: BitSetMask,
+{
+    /// Insert `value` at `key`, emitting `Inserted` or `Modified` depending on whether the key was present.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: C::Item,
+    ) -> Result<Option<C::Item>, BitSetCollectionError> {
+        let index = key_index(key)?;
+        let present = self.collection.bitset.contains(index);
+        let previous = self.collection.try_insert(key, value)?;
+
+        if present {
+            self.emit(ChangeEvent::Modified(key));
+        } else {
+            self.emit(ChangeEvent::Inserted(key));
+        }
+        Ok(previous)
+    }
+
+    /// Insert `value` at `key`, emitting `Inserted` or `Modified` depending on whether the key was present.
+    ///
+    /// Panics under the same conditions as `try_insert` fails.
+    pub fn insert(&mut self, key: K, value: C::Item) -> Option<C::Item> {
+        self.try_insert(key, value).unwrap()
+    }
+
+    /// Remove and return the value at `key`, emitting `Removed` with a clone of it if it was present.
+    ///
+    /// The value is only cloned while a reader is registered.
+    pub fn try_remove(&mut self, key: &K) -> Result<Option<C::Item>, BitSetCollectionError>
+    where
+        C::Item: Clone,
+    {
+        let value = self.collection.try_remove(key)?;
+        if let Some(value) = &value {
+            self.emit_with(|| ChangeEvent::Removed(*key, value.clone()));
+        }
+        Ok(value)
+    }
+
+    /// Remove and return the value at `key`, emitting `Removed` with a clone of it if it was present.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn remove(&mut self, key: &K) -> Option<C::Item>
+    where
+        C::Item: Clone,
+    {
+        self.try_remove(key).unwrap()
+    }
+
+    /// Remove every key, emitting `Removed` for each in ascending key order.
+    pub fn clear(&mut self)
+    where
+        M: Default,
+    {
+        let removed = self.collection.drain().collect::<Vec<_>>();
+        for (key, value) in removed {
+            self.emit(ChangeEvent::Removed(key, value));
+        }
+    }
+}
+
+impl<'a, K, C, M> Observed<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Fetch a mutable reference to the value at `key`, emitting `Modified` if it is present.
+    pub fn try_get_mut(&mut self, key: &K) -> Result<Option<&mut C::Item>, BitSetCollectionError> {
+        if self.collection.bitset.contains(key_index(*key)?) {
+            self.emit(ChangeEvent::Modified(*key));
+        }
+        self.collection.try_get_mut(key)
+    }
+
+    /// Fetch a mutable reference to the value at `key`, emitting `Modified` if it is present.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut C::Item> {
+        self.try_get_mut(key).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BitSetVec, ChangeEvent, Observed};
+
+    #[test]
+    fn observed_vec_independent_readers() {
+        let mut collection = Observed::<usize, Vec<&str>>::default();
+        collection.insert(0, "dropped");
+
+        let render = collection.register_reader();
+        collection.insert(1, "a");
+        collection.insert(1, "b");
+
+        let network = collection.register_reader();
+        *collection.get_mut(&1).unwrap() = "c";
+        assert_eq!(collection.remove(&1), Some("c"));
+        assert_eq!(collection.remove(&1), None);
+
+        assert_eq!(
+            collection.read(&render).cloned().collect::<Vec<_>>(),
+            vec![
+                ChangeEvent::Inserted(1),
+                ChangeEvent::Modified(1),
+                ChangeEvent::Modified(1),
+                ChangeEvent::Removed(1, "c"),
+            ]
+        );
+        assert_eq!(collection.read(&render).count(), 0);
+
+        collection.clear();
+        assert_eq!(
+            collection.read(&network).cloned().collect::<Vec<_>>(),
+            vec![
+                ChangeEvent::Modified(1),
+                ChangeEvent::Removed(1, "c"),
+                ChangeEvent::Removed(0, "dropped"),
+            ]
+        );
+
+        collection.remove_reader(network);
+        assert_eq!(
+            collection.read(&render).cloned().collect::<Vec<_>>(),
+            vec![ChangeEvent::Removed(0, "dropped")]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Reader is not registered with this collection")]
+    fn foreign_reader_rejected() {
+        let mut first = Observed::new(BitSetVec::<usize, u8>::default());
+        let mut second = Observed::new(BitSetVec::<usize, u8>::default());
+        let _ = first.register_reader();
+        let reader = second.register_reader();
+        first.read(&reader);
+    }
+
+    #[test]
+    fn unobserved_remove_skips_clone() {
+        #[derive(Debug, Default, PartialEq)]
+        struct Unclonable;
+
+        impl Clone for Unclonable {
+            fn clone(&self) -> Self {
+                panic!("Removed value cloned without a reader");
+            }
+        }
+
+        let mut observed = Observed::new(BitSetVec::<usize, Unclonable>::default());
+        observed.insert(2, Unclonable);
+        assert_eq!(observed.remove(&2), Some(Unclonable));
+
+        let reader = observed.register_reader();
+        assert_eq!(observed.read(&reader).count(), 0);
+    }
+}
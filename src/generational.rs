@@ -0,0 +1,385 @@
+use alloc::vec::Vec;
+
+use hibitset::{BitSet, BitSetLike};
+
+use collection_trait::Collection;
+
+use crate::{
+    key_index, BitSetCollection, BitSetCollectionError, BitSetCollectionIterator, BitSetKey,
+    BitSetMask, CollectionMut, StableCollection,
+};
+
+/// Handle pairing a slot index with the generation of the value it refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GenerationalKey<K> {
+    pub index: K,
+    pub generation: u32,
+}
+
+impl<K> GenerationalKey<K> {
+    pub fn new(index: K, generation: u32) -> Self {
+        GenerationalKey { index, generation }
+    }
+}
+
+/// Value displaced from a slot by `GenerationalCollection::try_insert`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Displaced<K, V> {
+    /// The slot held a value under the inserted key's own generation, which was replaced.
+    Replaced(V),
+    /// The slot held a value under an older generation, which was retired along with its key.
+    Retired(GenerationalKey<K>, V),
+}
+
+/// Whether `generation` is older than `current`, treating generations as a wrapping sequence.
+fn is_older(generation: u32, current: u32) -> bool {
+    (generation.wrapping_sub(current) as i32) < 0
+}
+
+/// `BitSetCollection` addressed by `GenerationalKey`s.
+///
+/// The bitset tracks which indices are occupied, while a per-slot generation counter
+/// turns lookups through stale handles into misses instead of returning whatever now lives in the slot.
+/// A slot's generation advances whenever its value is removed, or when a newer generation is inserted over it.
+/// Generations wrap around after `u32::MAX`, and are ordered so that the half of the range
+/// ahead of a slot's generation counts as newer and the half behind it as older.
+#[derive(Debug, Clone)]
+pub struct GenerationalCollection<'a, K, C, M = BitSet>
+where
+    C: Collection<'a, K>,
+{
+    collection: BitSetCollection<'a, K, C, M>,
+    generations: Vec<u32>,
+}
+
+impl<'a, K, C, M> Default for GenerationalCollection<'a, K, C, M>
+where
+    C: Collection<'a, K> + Default,
+    M: Default,
+{
+    fn default() -> Self {
+        GenerationalCollection {
+            collection: Default::default(),
+            generations: Vec::new(),
+        }
+    }
+}
+
+impl<'a, K, C, M> GenerationalCollection<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetMask,
+{
+    /// Wrap `collection` without marking any of its keys as present.
+    pub fn with_collection(collection: C) -> Self
+    where
+        M: Default,
+    {
+        GenerationalCollection {
+            collection: BitSetCollection::with_collection(collection),
+            generations: Vec::new(),
+        }
+    }
+
+    /// Number of occupied slots.
+    pub fn len(&self) -> usize {
+        self.collection.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.collection.is_empty()
+    }
+
+    /// Current generation of the slot at `index`.
+    fn slot_generation(&self, index: u32) -> u32 {
+        <[u32]>::get(&self.generations, index as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Set the generation of the slot at `index`, growing the generation table as needed.
+    fn set_slot_generation(&mut self, index: u32, generation: u32) {
+        let slot = index as usize;
+        if slot >= self.generations.len() {
+            self.generations.resize(slot + 1, 0);
+        }
+        self.generations[slot] = generation;
+    }
+}
+
+impl<'a, K, C, M> GenerationalCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetMask,
+{
+    /// Current generation of the slot at `index`.
+    ///
+    /// Slots that have never been used are at generation 0.
+    pub fn try_generation(&self, index: K) -> Result<u32, BitSetCollectionError> {
+        Ok(self.slot_generation(key_index(index)?))
+    }
+
+    /// Fetch the value at `key`, returning `None` if its generation is stale.
+    pub fn try_get(
+        &'a self,
+        key: &GenerationalKey<K>,
+    ) -> Result<Option<&'a C::Item>, BitSetCollectionError> {
+        if self.slot_generation(key_index(key.index)?) == key.generation {
+            self.collection.try_get(&key.index)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Fetch the value at `key`, returning `None` if its generation is stale.
+    ///
+    /// Panics if the key's index cannot be represented as a bitset index.
+    pub fn get(&'a self, key: &GenerationalKey<K>) -> Option<&'a C::Item> {
+        self.try_get(key).unwrap()
+    }
+
+    /// Check whether `key` is present at its current generation.
+    pub fn try_contains_key(
+        &self,
+        key: &GenerationalKey<K>,
+    ) -> Result<bool, BitSetCollectionError> {
+        let index = key_index(key.index)?;
+        Ok(self.slot_generation(index) == key.generation && self.collection.bitset.contains(index))
+    }
+
+    /// Check whether `key` is present at its current generation.
+    ///
+    /// Panics if the key's index cannot be represented as a bitset index.
+    pub fn contains_key(&self, key: &GenerationalKey<K>) -> bool {
+        self.try_contains_key(key).unwrap()
+    }
+
+    /// Iterate over the keys of occupied slots, tagged with their current generation, and their values.
+    pub fn iter(&'a self) -> GenerationalIter<'a, K, C, M> {
+        GenerationalIter {
+            iter: BitSetCollectionIterator::new(&self.collection),
+            generations: &self.generations,
+        }
+    }
+}
+
+impl<'a, K, C, M> GenerationalCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: StableCollection<'a, K>,
+    M: BitSetMask,
+{
+    /// Insert `value` at `key`, returning the value it displaced from the slot, if any.
+    ///
+    /// Inserting a newer generation than the slot's current one retires the older value,
+    /// which is returned as `Displaced::Retired` so its owner can be cleaned up.
+    /// Fails if the key's generation is older than the slot's.
+    pub fn try_insert(
+        &mut self,
+        key: GenerationalKey<K>,
+        value: C::Item,
+    ) -> Result<Option<Displaced<K, C::Item>>, BitSetCollectionError> {
+        let index = key_index(key.index)?;
+        let generation = self.slot_generation(index);
+
+        if is_older(key.generation, generation) {
+            return Err(BitSetCollectionError::StaleGeneration(index));
+        }
+
+        let previous = self.collection.try_insert(key.index, value)?;
+        if key.generation == generation {
+            Ok(previous.map(Displaced::Replaced))
+        } else {
+            self.set_slot_generation(index, key.generation);
+            let retired = GenerationalKey::new(key.index, generation);
+            Ok(previous.map(|value| Displaced::Retired(retired, value)))
+        }
+    }
+
+    /// Insert `value` at `key`, returning the value it displaced from the slot, if any.
+    ///
+    /// Panics under the same conditions as `try_insert` fails.
+    pub fn insert(
+        &mut self,
+        key: GenerationalKey<K>,
+        value: C::Item,
+    ) -> Option<Displaced<K, C::Item>> {
+        self.try_insert(key, value).unwrap()
+    }
+
+    /// Remove and return the value at `key`, advancing the slot's generation.
+    ///
+    /// Returns `None` without touching the slot if the key's generation is stale.
+    pub fn try_remove(
+        &mut self,
+        key: &GenerationalKey<K>,
+    ) -> Result<Option<C::Item>, BitSetCollectionError> {
+        let index = key_index(key.index)?;
+        if self.slot_generation(index) != key.generation {
+            return Ok(None);
+        }
+
+        let value = self.collection.try_remove(&key.index)?;
+        if value.is_some() {
+            self.set_slot_generation(index, key.generation.wrapping_add(1));
+        }
+        Ok(value)
+    }
+
+    /// Remove and return the value at `key`.
+    ///
+    /// Panics if the key's index cannot be represented as a bitset index.
+    pub fn remove(&mut self, key: &GenerationalKey<K>) -> Option<C::Item> {
+        self.try_remove(key).unwrap()
+    }
+
+    /// Remove every value, advancing the generation of each occupied slot.
+    pub fn clear(&mut self) {
+        let indices = (&self.collection.bitset).iter().collect::<Vec<_>>();
+        for index in indices {
+            let generation = self.slot_generation(index);
+            self.set_slot_generation(index, generation.wrapping_add(1));
+        }
+        self.collection.clear();
+    }
+}
+
+impl<'a, K, C, M> GenerationalCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Fetch a mutable reference to the value at `key`, returning `None` if its generation is stale.
+    pub fn try_get_mut(
+        &mut self,
+        key: &GenerationalKey<K>,
+    ) -> Result<Option<&mut C::Item>, BitSetCollectionError> {
+        if self.slot_generation(key_index(key.index)?) == key.generation {
+            self.collection.try_get_mut(&key.index)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Fetch a mutable reference to the value at `key`, returning `None` if its generation is stale.
+    ///
+    /// Panics if the key's index cannot be represented as a bitset index.
+    pub fn get_mut(&mut self, key: &GenerationalKey<K>) -> Option<&mut C::Item> {
+        self.try_get_mut(key).unwrap()
+    }
+}
+
+/// Iterator over the occupied slots of a `GenerationalCollection` in ascending index order.
+pub struct GenerationalIter<'a, K, C, M = BitSet> {
+    iter: BitSetCollectionIterator<'a, K, C, M>,
+    generations: &'a [u32],
+}
+
+impl<'a, K, C, M> Iterator for GenerationalIter<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    type Item = (GenerationalKey<K>, &'a C::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let generations = self.generations;
+        self.iter.next().map(|(key, value)| {
+            let index = key
+                .to_index()
+                .expect("Bitset index does not round-trip to its key type");
+            let generation = generations.get(index as usize).copied().unwrap_or(0);
+            (GenerationalKey::new(key, generation), value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BitSetCollectionError, Displaced, GenerationalCollection, GenerationalKey};
+
+    type GenerationalVec<'a> = GenerationalCollection<'a, usize, Vec<&'static str>>;
+
+    #[test]
+    fn stale_keys_miss() {
+        let mut collection = GenerationalVec::default();
+        let alice = GenerationalKey::new(0, 0);
+        collection.insert(alice, "alice");
+
+        assert_eq!(collection.remove(&alice), Some("alice"));
+        assert_eq!(collection.try_generation(0), Ok(1));
+
+        let bob = GenerationalKey::new(0, 1);
+        collection.insert(bob, "bob");
+        assert_eq!(collection.get(&alice), None);
+        assert_eq!(collection.remove(&alice), None);
+        assert_eq!(collection.get(&bob), Some(&"bob"));
+        assert!(!collection.contains_key(&alice));
+        assert_eq!(
+            collection.try_insert(alice, "alice"),
+            Err(BitSetCollectionError::StaleGeneration(0))
+        );
+        assert_eq!(collection.get(&bob), Some(&"bob"));
+    }
+
+    #[test]
+    fn newer_generation_retires_slot() {
+        let mut collection = GenerationalVec::default();
+        collection.insert(GenerationalKey::new(2, 0), "old");
+        assert_eq!(
+            collection.insert(GenerationalKey::new(2, 3), "new"),
+            Some(Displaced::Retired(GenerationalKey::new(2, 0), "old"))
+        );
+        assert_eq!(
+            collection.insert(GenerationalKey::new(2, 3), "new"),
+            Some(Displaced::Replaced("new"))
+        );
+        assert_eq!(collection.get(&GenerationalKey::new(2, 0)), None);
+        *collection.get_mut(&GenerationalKey::new(2, 3)).unwrap() = "newer";
+        collection.insert(GenerationalKey::new(5, 0), "other");
+
+        assert_eq!(
+            collection.iter().collect::<Vec<_>>(),
+            vec![
+                (GenerationalKey::new(2, 3), &"newer"),
+                (GenerationalKey::new(5, 0), &"other")
+            ]
+        );
+
+        collection.clear();
+        assert!(collection.is_empty());
+        assert_eq!(collection.try_generation(2), Ok(4));
+        assert_eq!(collection.try_generation(5), Ok(1));
+    }
+
+    #[test]
+    fn generations_wrap_around() {
+        let mut collection = GenerationalVec::default();
+        collection.insert(GenerationalKey::new(1, i32::MAX as u32), "early");
+        collection.insert(GenerationalKey::new(1, u32::MAX - 1), "late");
+        collection.remove(&GenerationalKey::new(1, u32::MAX - 1));
+
+        let last = GenerationalKey::new(1, u32::MAX);
+        collection.insert(last, "last");
+        assert_eq!(collection.remove(&last), Some("last"));
+        assert_eq!(collection.try_generation(1), Ok(0));
+
+        let wrapped = GenerationalKey::new(1, 0);
+        collection.insert(wrapped, "wrapped");
+        assert_eq!(collection.get(&wrapped), Some(&"wrapped"));
+        assert_eq!(
+            collection.try_insert(last, "last"),
+            Err(BitSetCollectionError::StaleGeneration(1))
+        );
+
+        assert_eq!(
+            collection.insert(GenerationalKey::new(1, 2), "newer"),
+            Some(Displaced::Retired(wrapped, "wrapped"))
+        );
+        assert_eq!(collection.get(&wrapped), None);
+        assert_eq!(collection.get(&GenerationalKey::new(1, 2)), Some(&"newer"));
+    }
+}
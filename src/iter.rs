@@ -0,0 +1,459 @@
+use core::marker::PhantomData;
+
+use hibitset::{BitIter, BitSet, BitSetLike, BitSetNot};
+
+use collection_trait::Collection;
+
+use crate::{
+    navigation::{next_block, next_index, next_index_linear},
+    BitSetCollection, BitSetKey, CollectionMut, StableCollection, BLOCK_BITS,
+};
+
+pub struct BitSetCollectionIterator<'a, K, C, M = BitSet> {
+    key_iter: BitIter<&'a M>,
+    collection: &'a C,
+    _phantom: PhantomData<&'a K>,
+}
+
+impl<'a, K, C, M> BitSetCollectionIterator<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    pub fn new(collection: &'a BitSetCollection<'a, K, C, M>) -> Self {
+        let key_iter = (&collection.bitset).iter();
+        let collection = &collection.collection;
+
+        BitSetCollectionIterator {
+            key_iter,
+            collection,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<'a, K, C, M> Iterator for BitSetCollectionIterator<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetLike,
+    K: BitSetKey,
+{
+    type Item = (K, &'a C::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.key_iter.next().map(|index| {
+            let key = K::from_index(index);
+            (key, self.collection.get_unchecked(&key))
+        })
+    }
+}
+
+/// Iterator over the present keys of a `BitSetCollection` within a range, in ascending key order.
+pub struct BitSetCollectionRange<'a, K, C, M = BitSet> {
+    bitset: &'a M,
+    collection: &'a C,
+    start: u32,
+    end: u32,
+    _phantom: PhantomData<&'a K>,
+}
+
+impl<'a, K, C, M> BitSetCollectionRange<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    /// Iterate over the indices of `collection` in `start..end`.
+    pub(crate) fn new(collection: &'a BitSetCollection<'a, K, C, M>, start: u32, end: u32) -> Self {
+        BitSetCollectionRange {
+            bitset: &collection.bitset,
+            collection: &collection.collection,
+            start,
+            end,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<'a, K, C, M> Iterator for BitSetCollectionRange<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    K: BitSetKey,
+    M: BitSetLike,
+{
+    type Item = (K, &'a C::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start >= self.end {
+            return None;
+        }
+
+        let index = next_index(self.bitset, self.start).filter(|index| *index < self.end);
+        self.start = index.map_or(self.end, |index| index + 1);
+        index.map(|index| {
+            let key = K::from_index(index);
+            (key, self.collection.get_unchecked(&key))
+        })
+    }
+}
+
+/// Iterator over the keys absent from a `BitSetCollection` within a range, in ascending key order.
+///
+/// Free indices that do not map to a key, such as index 0 for `NonZero` keys, are skipped.
+pub struct BitSetCollectionFreeKeys<'a, K, M = BitSet>
+where
+    M: BitSetLike,
+{
+    mask: BitSetNot<&'a M>,
+    start: u32,
+    end: u32,
+    _phantom: PhantomData<K>,
+}
+
+impl<'a, K, M> BitSetCollectionFreeKeys<'a, K, M>
+where
+    M: BitSetLike,
+{
+    /// Iterate over the indices in `start..end` absent from `bitset`.
+    pub(crate) fn new(bitset: &'a M, start: u32, end: u32) -> Self {
+        BitSetCollectionFreeKeys {
+            mask: BitSetNot(bitset),
+            start,
+            end,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<'a, K, M> Iterator for BitSetCollectionFreeKeys<'a, K, M>
+where
+    K: BitSetKey,
+    M: BitSetLike,
+{
+    type Item = K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(index) = next_index_linear(&self.mask, self.start, self.end) {
+            self.start = index + 1;
+            if let Some(key) = K::try_from_index(index) {
+                return Some(key);
+            }
+        }
+        self.start = self.end;
+        None
+    }
+}
+
+/// Iterator over the non-empty blocks of `BLOCK_BITS` keys in a `BitSetCollection`, in ascending key order.
+///
+/// Yields the bitset index of each block's first key alongside its occupancy word,
+/// in which bit `i` is set if the key at index `base + i` is present.
+/// The base is left as an index, since the key type need not be able to represent it, as with `NonZero` keys.
+pub struct BitSetCollectionBlocks<'a, M = BitSet> {
+    bitset: &'a M,
+    block: u32,
+}
+
+impl<'a, M> BitSetCollectionBlocks<'a, M>
+where
+    M: BitSetLike,
+{
+    pub(crate) fn new(bitset: &'a M) -> Self {
+        BitSetCollectionBlocks { bitset, block: 0 }
+    }
+}
+
+impl<'a, M> Iterator for BitSetCollectionBlocks<'a, M>
+where
+    M: BitSetLike,
+{
+    type Item = (u32, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (block, word) = next_block(self.bitset, self.block)?;
+        self.block = block + 1;
+        Some((block * BLOCK_BITS, word))
+    }
+}
+
+/// Iterator over the non-empty blocks of a slice-backed `BitSetCollection`, in ascending key order.
+///
+/// Like `BitSetCollectionBlocks`, but also yields the values stored for each block's keys.
+/// The slice is cut short where the backing storage ends, and its element `i` corresponds to bit `i` of the word.
+pub struct BitSetCollectionBlockSlices<'a, V, M = BitSet> {
+    blocks: BitSetCollectionBlocks<'a, M>,
+    values: &'a [V],
+}
+
+impl<'a, V, M> BitSetCollectionBlockSlices<'a, V, M>
+where
+    M: BitSetLike,
+{
+    pub(crate) fn new(bitset: &'a M, values: &'a [V]) -> Self {
+        BitSetCollectionBlockSlices {
+            blocks: BitSetCollectionBlocks::new(bitset),
+            values,
+        }
+    }
+}
+
+impl<'a, V, M> Iterator for BitSetCollectionBlockSlices<'a, V, M>
+where
+    M: BitSetLike,
+{
+    type Item = (u32, u64, &'a [V]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.blocks.next().map(|(base, word)| {
+            let len = self.values.len();
+            let start = (base as usize).min(len);
+            let end = (base as usize + BLOCK_BITS as usize).min(len);
+            (base, word, &self.values[start..end])
+        })
+    }
+}
+
+/// Iterator over the non-empty blocks of a slice-backed `BitSetCollection`
+/// and mutable references to their values, in ascending key order.
+pub struct BitSetCollectionBlockSlicesMut<'b, V, M = BitSet> {
+    blocks: BitSetCollectionBlocks<'b, M>,
+    values: &'b mut [V],
+    /// Index of the key stored at the front of `values`.
+    offset: usize,
+}
+
+impl<'b, V, M> BitSetCollectionBlockSlicesMut<'b, V, M>
+where
+    M: BitSetLike,
+{
+    pub(crate) fn new(bitset: &'b M, values: &'b mut [V]) -> Self {
+        BitSetCollectionBlockSlicesMut {
+            blocks: BitSetCollectionBlocks::new(bitset),
+            values,
+            offset: 0,
+        }
+    }
+}
+
+impl<'b, V, M> Iterator for BitSetCollectionBlockSlicesMut<'b, V, M>
+where
+    M: BitSetLike,
+{
+    type Item = (u32, u64, &'b mut [V]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (base, word) = self.blocks.next()?;
+
+        // Blocks are yielded in ascending order, so each one can be split off the front of the remaining values.
+        let values = core::mem::take(&mut self.values);
+        let skip = (base as usize - self.offset).min(values.len());
+        let (_, values) = values.split_at_mut(skip);
+        let (block, rest) = values.split_at_mut((BLOCK_BITS as usize).min(values.len()));
+        self.offset += skip + block.len();
+        self.values = rest;
+
+        Some((base, word, block))
+    }
+}
+
+/// Iterator yielding mutable references to the values of a `BitSetCollection` in ascending key order.
+pub struct BitSetCollectionIterMut<'a, 'b, K, C, M = BitSet>
+where
+    C: CollectionMut<'a, K>,
+{
+    key_iter: BitIter<&'b M>,
+    raw: C::Raw,
+    _phantom: PhantomData<(&'b mut C, &'a K)>,
+}
+
+impl<'a, 'b, K, C, M> BitSetCollectionIterMut<'a, 'b, K, C, M>
+where
+    C: CollectionMut<'a, K>,
+    M: BitSetLike,
+{
+    pub fn new(collection: &'b mut BitSetCollection<'a, K, C, M>) -> Self {
+        let key_iter = (&collection.bitset).iter();
+        let raw = collection.collection.raw();
+
+        BitSetCollectionIterMut {
+            key_iter,
+            raw,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<'a, 'b, K, C, M> Iterator for BitSetCollectionIterMut<'a, 'b, K, C, M>
+where
+    C: CollectionMut<'a, K>,
+    M: BitSetLike,
+    C::Item: 'b,
+    K: BitSetKey,
+{
+    type Item = (K, &'b mut C::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.key_iter.next().map(|index| {
+            let key = K::from_index(index);
+            // Safety: the bitset yields each key at most once, and `CollectionMut`
+            // guarantees distinct keys map to disjoint values.
+            (key, unsafe { &mut *C::get_raw_mut(&self.raw, &key) })
+        })
+    }
+}
+
+/// Iterator over the values of a `BitSetCollection` in ascending key order.
+pub struct BitSetCollectionValues<'a, K, C, M = BitSet>(BitSetCollectionIterator<'a, K, C, M>);
+
+impl<'a, K, C, M> BitSetCollectionValues<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    pub fn new(collection: &'a BitSetCollection<'a, K, C, M>) -> Self {
+        BitSetCollectionValues(BitSetCollectionIterator::new(collection))
+    }
+}
+
+impl<'a, K, C, M> Iterator for BitSetCollectionValues<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetLike,
+    K: BitSetKey,
+{
+    type Item = &'a C::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, value)| value)
+    }
+}
+
+/// Iterator over mutable references to the values of a `BitSetCollection` in ascending key order.
+pub struct BitSetCollectionValuesMut<'a, 'b, K, C, M = BitSet>(
+    BitSetCollectionIterMut<'a, 'b, K, C, M>,
+)
+where
+    C: CollectionMut<'a, K>;
+
+impl<'a, 'b, K, C, M> BitSetCollectionValuesMut<'a, 'b, K, C, M>
+where
+    C: CollectionMut<'a, K>,
+    M: BitSetLike,
+{
+    pub fn new(collection: &'b mut BitSetCollection<'a, K, C, M>) -> Self {
+        BitSetCollectionValuesMut(BitSetCollectionIterMut::new(collection))
+    }
+}
+
+impl<'a, 'b, K, C, M> Iterator for BitSetCollectionValuesMut<'a, 'b, K, C, M>
+where
+    C: CollectionMut<'a, K>,
+    M: BitSetLike,
+    C::Item: 'b,
+    K: BitSetKey,
+{
+    type Item = &'b mut C::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, value)| value)
+    }
+}
+
+/// Draining iterator yielding the owned key/value pairs of a `BitSetCollection` in ascending key order.
+pub struct BitSetCollectionDrain<'a, 'b, K, C, M = BitSet>
+where
+    C: StableCollection<'a, K>,
+{
+    key_iter: BitIter<M>,
+    collection: &'b mut C,
+    _phantom: PhantomData<&'a K>,
+}
+
+impl<'a, 'b, K, C, M> BitSetCollectionDrain<'a, 'b, K, C, M>
+where
+    C: StableCollection<'a, K>,
+    M: BitSetLike + Default,
+{
+    pub fn new(collection: &'b mut BitSetCollection<'a, K, C, M>) -> Self {
+        let key_iter = core::mem::take(&mut collection.bitset).iter();
+        collection.len = 0;
+
+        BitSetCollectionDrain {
+            key_iter,
+            collection: &mut collection.collection,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<'a, 'b, K, C, M> Iterator for BitSetCollectionDrain<'a, 'b, K, C, M>
+where
+    C: StableCollection<'a, K>,
+    M: BitSetLike,
+    K: BitSetKey,
+{
+    type Item = (K, C::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.key_iter.next().map(|index| {
+            let key = K::from_index(index);
+            let value = self
+                .collection
+                .stable_remove(&key)
+                .expect("Key present in bitset but not in collection");
+            (key, value)
+        })
+    }
+}
+
+impl<'a, 'b, K, C, M> Drop for BitSetCollectionDrain<'a, 'b, K, C, M>
+where
+    C: StableCollection<'a, K>,
+{
+    fn drop(&mut self) {
+        self.collection.stable_clear();
+    }
+}
+
+/// Owning iterator yielding the key/value pairs of a `BitSetCollection` in ascending key order.
+pub struct BitSetCollectionIntoIter<'a, K, C, M = BitSet>
+where
+    C: StableCollection<'a, K>,
+{
+    key_iter: BitIter<M>,
+    collection: C,
+    _phantom: PhantomData<&'a K>,
+}
+
+impl<'a, K, C, M> BitSetCollectionIntoIter<'a, K, C, M>
+where
+    C: StableCollection<'a, K>,
+    M: BitSetLike,
+{
+    pub fn new(collection: BitSetCollection<'a, K, C, M>) -> Self {
+        BitSetCollectionIntoIter {
+            key_iter: collection.bitset.iter(),
+            collection: collection.collection,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<'a, K, C, M> Iterator for BitSetCollectionIntoIter<'a, K, C, M>
+where
+    C: StableCollection<'a, K>,
+    K: BitSetKey,
+    M: BitSetLike,
+{
+    type Item = (K, C::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.key_iter.next().map(|index| {
+            let key = K::from_index(index);
+            let value = self
+                .collection
+                .stable_remove(&key)
+                .expect("Key present in bitset but not in collection");
+            (key, value)
+        })
+    }
+}
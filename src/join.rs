@@ -0,0 +1,367 @@
+use hibitset::{BitIter, BitSetAll, BitSetAnd, BitSetLike, BitSetNot};
+
+use collection_trait::Collection;
+
+use crate::{BitSetCollection, BitSetKey, CollectionMut};
+
+/// A single participant in a `Join`.
+///
+/// Implemented for shared and mutable references to `BitSetCollection`.
+pub trait Joinable {
+    type Key;
+    type Mask: BitSetLike;
+    type Value;
+    type Item;
+
+    /// Split the participant into the mask of keys it can yield and a handle used to fetch their values.
+    fn open(self) -> (Self::Mask, Self::Value);
+
+    /// Take only the mask of keys the participant can yield, without preparing a handle to its values.
+    fn mask(self) -> Self::Mask;
+
+    /// Fetch the item at `key` through `value`.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be the bitset index of `key` and be present in the mask returned by `open`,
+    /// and may only be fetched once for participants that yield mutable references.
+    unsafe fn fetch(value: &Self::Value, index: u32, key: Self::Key) -> Self::Item;
+}
+
+impl<'a, K, C, M> Joinable for &'a BitSetCollection<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    type Key = K;
+    type Mask = &'a M;
+    type Value = &'a C;
+    type Item = &'a C::Item;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (&self.bitset, &self.collection)
+    }
+
+    fn mask(self) -> Self::Mask {
+        &self.bitset
+    }
+
+    unsafe fn fetch(value: &Self::Value, _: u32, key: K) -> Self::Item {
+        value.get_unchecked(&key)
+    }
+}
+
+/// Opening takes `CollectionMut::raw`, so map-backed participants pay for a pointer table over every value they hold,
+/// even if the join's other masks leave only a few keys to visit.
+impl<'a, 'b, K, C, M> Joinable for &'b mut BitSetCollection<'a, K, C, M>
+where
+    C: CollectionMut<'a, K>,
+    C::Item: 'b,
+    M: BitSetLike,
+{
+    type Key = K;
+    type Mask = &'b M;
+    type Value = C::Raw;
+    type Item = &'b mut C::Item;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (&self.bitset, self.collection.raw())
+    }
+
+    fn mask(self) -> Self::Mask {
+        &self.bitset
+    }
+
+    unsafe fn fetch(value: &Self::Value, _: u32, key: K) -> Self::Item {
+        &mut *C::get_raw_mut(value, &key)
+    }
+}
+
+/// Join participant matching only keys absent from the wrapped participant.
+///
+/// Yields `()` in place of a value.
+/// A join should include at least one non-negated participant, as the mask alone spans the entire key space.
+pub struct Without<J>(pub J);
+
+impl<J> Joinable for Without<J>
+where
+    J: Joinable,
+{
+    type Key = J::Key;
+    type Mask = BitSetNot<J::Mask>;
+    type Value = ();
+    type Item = ();
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (self.mask(), ())
+    }
+
+    fn mask(self) -> Self::Mask {
+        BitSetNot(self.0.mask())
+    }
+
+    unsafe fn fetch(_: &Self::Value, _: u32, _: Self::Key) -> Self::Item {}
+}
+
+/// Join participant yielding `Some` for keys present in the wrapped participant and `None` otherwise.
+///
+/// Does not restrict the keys of the join it takes part in.
+pub struct Maybe<J>(pub J);
+
+impl<J> Joinable for Maybe<J>
+where
+    J: Joinable,
+{
+    type Key = J::Key;
+    type Mask = BitSetAll;
+    type Value = (J::Mask, J::Value);
+    type Item = Option<J::Item>;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (BitSetAll, self.0.open())
+    }
+
+    fn mask(self) -> Self::Mask {
+        BitSetAll
+    }
+
+    unsafe fn fetch((mask, value): &Self::Value, index: u32, key: Self::Key) -> Self::Item {
+        if mask.contains(index) {
+            Some(J::fetch(value, index, key))
+        } else {
+            None
+        }
+    }
+}
+
+/// Tuple of `Joinable` participants sharing a key type,
+/// iterated over the intersection of their masks.
+///
+/// `(&a, &b, &mut c).join()` yields `(K, &A, &B, &mut C)` for every key present in all three collections.
+pub trait Join: Sized {
+    type Key;
+    type Mask: BitSetLike;
+    type Values;
+    type Item;
+
+    /// Split the participants into their combined mask and per-participant value handles.
+    fn open(self) -> (Self::Mask, Self::Values);
+
+    /// Fetch the joined item at `index`.
+    ///
+    /// # Safety
+    ///
+    /// See `Joinable::fetch`.
+    unsafe fn fetch(values: &Self::Values, index: u32) -> Self::Item;
+
+    /// Iterate over the keys present in every participant in ascending order.
+    fn join(self) -> JoinIter<Self> {
+        JoinIter::new(self)
+    }
+}
+
+/// Iterator over the items of a `Join`.
+pub struct JoinIter<J>
+where
+    J: Join,
+{
+    key_iter: BitIter<J::Mask>,
+    values: J::Values,
+}
+
+impl<J> JoinIter<J>
+where
+    J: Join,
+{
+    pub fn new(join: J) -> Self {
+        let (mask, values) = join.open();
+        JoinIter {
+            key_iter: mask.iter(),
+            values,
+        }
+    }
+}
+
+impl<J> Iterator for JoinIter<J>
+where
+    J: Join,
+{
+    type Item = J::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let values = &self.values;
+        // Safety: `BitIter` yields each index in the mask exactly once.
+        self.key_iter
+            .next()
+            .map(|index| unsafe { J::fetch(values, index) })
+    }
+}
+
+macro_rules! and_mask_type {
+    ($t:ident) => {
+        <$t as Joinable>::Mask
+    };
+    ($t:ident, $($rest:ident),+) => {
+        BitSetAnd<<$t as Joinable>::Mask, and_mask_type!($($rest),+)>
+    };
+}
+
+macro_rules! and_mask {
+    ($mask:expr) => {
+        $mask
+    };
+    ($mask:expr, $($rest:expr),+) => {
+        BitSetAnd($mask, and_mask!($($rest),+))
+    };
+}
+
+macro_rules! impl_join {
+    ($($t:ident),+) => {
+        impl<K, $($t),+> Join for ($($t,)+)
+        where
+            K: BitSetKey,
+            $($t: Joinable<Key = K>,)+
+        {
+            type Key = K;
+            type Mask = and_mask_type!($($t),+);
+            type Values = ($($t::Value,)+);
+            type Item = (K, $($t::Item,)+);
+
+            #[allow(non_snake_case)]
+            fn open(self) -> (Self::Mask, Self::Values) {
+                let ($($t,)+) = self;
+                let ($($t,)+) = ($($t.open(),)+);
+                (and_mask!($($t.0),+), ($($t.1,)+))
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn fetch(values: &Self::Values, index: u32) -> Self::Item {
+                let key = K::from_index(index);
+                let ($($t,)+) = values;
+                (key, $($t::fetch($t, index, key),)+)
+            }
+        }
+
+        #[cfg(feature = "parallel")]
+        unsafe impl<K, $($t),+> crate::ParJoin for ($($t,)+)
+        where
+            K: BitSetKey,
+            $($t: crate::ParJoinable<Key = K>,)+
+        {
+        }
+    };
+}
+
+impl_join!(A);
+impl_join!(A, B);
+impl_join!(A, B, C);
+impl_join!(A, B, C, D);
+impl_join!(A, B, C, D, E);
+impl_join!(A, B, C, D, E, F);
+impl_join!(A, B, C, D, E, F, G);
+impl_join!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use collection_trait::Collection;
+
+    use crate::{BitSetBTreeMap, BitSetHashMap, BitSetVec, Join, Maybe, Without};
+
+    #[test]
+    fn join_mixed_backends() {
+        let mut positions = BitSetVec::<usize, f32>::default();
+        let mut velocities = BitSetBTreeMap::<usize, f32>::default();
+        let mut accelerations = BitSetHashMap::<usize, f32>::default();
+
+        for key in 0..6 {
+            positions.insert(key, 0.0);
+        }
+        for key in (0..6).step_by(2) {
+            velocities.insert(key, key as f32);
+        }
+        for key in (0..6).step_by(3) {
+            accelerations.insert(key, 1.0);
+        }
+
+        for (_, velocity, acceleration, position) in
+            (&velocities, &accelerations, &mut positions).join()
+        {
+            *position += velocity + acceleration;
+        }
+
+        assert_eq!(
+            positions.iter().map(|(_, v)| *v).collect::<Vec<_>>(),
+            vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            (&positions, &velocities)
+                .join()
+                .map(|(key, _, _)| key)
+                .collect::<Vec<_>>(),
+            vec![0, 2, 4]
+        );
+    }
+
+    #[test]
+    fn join_without_maybe() {
+        let mut positions = BitSetVec::<usize, f32>::default();
+        let mut frozen = BitSetBTreeMap::<usize, ()>::default();
+        let mut velocities = BitSetHashMap::<usize, f32>::default();
+
+        for key in 0..6 {
+            positions.insert(key, key as f32);
+        }
+        frozen.insert(1, ());
+        frozen.insert(4, ());
+        velocities.insert(0, 1.0);
+        velocities.insert(2, 2.0);
+        velocities.insert(4, 4.0);
+
+        let joined = (&positions, Without(&frozen), Maybe(&velocities))
+            .join()
+            .map(|(key, _, (), velocity)| (key, velocity.copied()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            joined,
+            vec![(0, Some(1.0)), (2, Some(2.0)), (3, None), (5, None)]
+        );
+
+        // Negating a mutable participant only needs its mask.
+        assert_eq!(
+            (&positions, Without(&mut frozen))
+                .join()
+                .map(|(key, _, ())| key)
+                .collect::<Vec<_>>(),
+            vec![0, 2, 3, 5]
+        );
+    }
+
+    #[test]
+    fn join_mutable_map_backends() {
+        let mut positions = BitSetBTreeMap::<usize, f32>::default();
+        let mut velocities = BitSetHashMap::<usize, f32>::default();
+        for key in 0..40 {
+            positions.insert(key, key as f32);
+            velocities.insert(key, 1.0);
+        }
+
+        // Every yielded reference stays alive while later ones are fetched.
+        let joined = (&mut positions, &mut velocities).join().collect::<Vec<_>>();
+        assert_eq!(joined.len(), 40);
+        for (_, position, velocity) in joined {
+            *position += *velocity;
+            *velocity = 0.0;
+        }
+
+        let values = positions.values_mut().collect::<Vec<_>>();
+        for value in values {
+            *value *= 2.0;
+        }
+
+        assert!(positions
+            .iter()
+            .all(|(key, value)| *value == (key as f32 + 1.0) * 2.0));
+        assert!(velocities.values().all(|value| *value == 0.0));
+    }
+}
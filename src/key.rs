@@ -0,0 +1,99 @@
+use core::num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize, Wrapping};
+
+/// Key type that can be stored in a `BitSetCollection`'s mask.
+///
+/// Implemented for the primitive integers, their non-zero counterparts and `Wrapping`,
+/// and derivable for newtypes around any other `BitSetKey` via `#[derive(BitSetKey)]` with the `derive` feature.
+pub trait BitSetKey: Copy {
+    /// Convert the key into its bitset index, or `None` if it cannot be represented as one.
+    fn to_index(self) -> Option<u32>;
+
+    /// Convert a bitset index back into its key, or `None` if no key maps onto it.
+    fn try_from_index(index: u32) -> Option<Self>;
+
+    /// Convert a bitset index produced by `to_index` back into its key.
+    ///
+    /// Panics if no key maps onto the index, so indices from any other source go through `try_from_index`.
+    fn from_index(index: u32) -> Self {
+        Self::try_from_index(index).expect("Bitset index does not round-trip to its key type")
+    }
+}
+
+macro_rules! impl_bitset_key_int {
+    ($($t:ty),+) => {
+        $(
+            impl BitSetKey for $t {
+                fn to_index(self) -> Option<u32> {
+                    core::convert::TryInto::try_into(self).ok()
+                }
+
+                fn try_from_index(index: u32) -> Option<Self> {
+                    core::convert::TryInto::try_into(index).ok()
+                }
+            }
+        )+
+    };
+}
+
+impl_bitset_key_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+macro_rules! impl_bitset_key_non_zero {
+    ($($t:ty),+) => {
+        $(
+            // Non-zero keys map onto the index of the same value, leaving index 0 unused.
+            impl BitSetKey for $t {
+                fn to_index(self) -> Option<u32> {
+                    self.get().to_index()
+                }
+
+                fn try_from_index(index: u32) -> Option<Self> {
+                    <$t>::new(BitSetKey::try_from_index(index)?)
+                }
+            }
+        )+
+    };
+}
+
+impl_bitset_key_non_zero!(NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroUsize);
+
+impl<T> BitSetKey for Wrapping<T>
+where
+    T: BitSetKey,
+{
+    fn to_index(self) -> Option<u32> {
+        self.0.to_index()
+    }
+
+    fn try_from_index(index: u32) -> Option<Self> {
+        T::try_from_index(index).map(Wrapping)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::{NonZeroU32, Wrapping};
+
+    use super::BitSetKey;
+
+    #[test]
+    fn signed_keys_reject_negatives() {
+        assert_eq!((-1i64).to_index(), None);
+        assert_eq!(7i64.to_index(), Some(7));
+        assert_eq!(i64::from_index(7), 7);
+    }
+
+    #[test]
+    fn non_zero_keys_round_trip() {
+        let key = NonZeroU32::new(3).unwrap();
+        assert_eq!(key.to_index(), Some(3));
+        assert_eq!(NonZeroU32::from_index(3), key);
+    }
+
+    #[test]
+    fn unmapped_indices_have_no_key() {
+        assert_eq!(NonZeroU32::try_from_index(0), None);
+        assert_eq!(u8::try_from_index(255), Some(255));
+        assert_eq!(u8::try_from_index(256), None);
+        assert_eq!(Wrapping::<u8>::try_from_index(256), None);
+    }
+}
@@ -1,155 +1,558 @@
-use std::{
-    convert::{TryFrom, TryInto},
-    fmt::Debug,
+// Items are taken from `core` and `alloc` wherever they live there, ahead of a `no_std` build.
+// That build still needs a mask that works without `std`, since hibitset 0.6 links it unconditionally.
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::{
+    hash::{Hash, Hasher},
     iter::FromIterator,
     marker::PhantomData,
+    ops::{Index, IndexMut},
 };
 
 use hibitset::{BitIter, BitSet, BitSetLike};
 
+// Lets `#[derive(BitSetKey)]` refer to this crate by name from within its own tests.
+extern crate self as bitset_collection;
+
 pub use collection_trait;
 use collection_trait::Collection;
 
+mod allocator;
+mod collection_mut;
+mod columns;
+mod concurrent;
+mod entry;
+mod error;
+mod events;
+mod generational;
+mod iter;
+mod join;
+mod key;
+mod mask;
+mod masked;
+mod navigation;
+#[cfg(feature = "parallel")]
+mod par;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod stable_collection;
+mod tracked;
+
+pub use allocator::KeyAllocator;
+#[cfg(feature = "derive")]
+pub use bitset_collection_derive::BitSetKey;
+pub use collection_mut::{CollectionMut, DisjointCollectionMut};
+pub use columns::{BitSetColumns, Column, ColumnMut, Columns};
+pub use concurrent::BitSetCollectionConcurrent;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use error::BitSetCollectionError;
+pub use events::{ChangeEvent, ChangeEvents, Observed, ReaderId};
+pub use generational::{Displaced, GenerationalCollection, GenerationalIter, GenerationalKey};
+pub use iter::{
+    BitSetCollectionBlockSlices, BitSetCollectionBlockSlicesMut, BitSetCollectionBlocks,
+    BitSetCollectionDrain, BitSetCollectionFreeKeys, BitSetCollectionIntoIter,
+    BitSetCollectionIterMut, BitSetCollectionIterator, BitSetCollectionRange,
+    BitSetCollectionValues, BitSetCollectionValuesMut,
+};
+pub use join::{Join, JoinIter, Joinable, Maybe, Without};
+pub use key::BitSetKey;
+pub use mask::BitSetMask;
+pub use masked::{Masked, MaskedKeys, MaskedMut};
+#[cfg(feature = "parallel")]
+pub use par::{JoinParIter, ParJoin, ParJoinable};
+pub use stable_collection::StableCollection;
+pub use tracked::{Changes, Tracked};
+
+/// Number of distinct indices a `hibitset::BitSet` can hold.
+pub const BITSET_CAPACITY: u32 = (BitSet::BITS_PER_USIZE as u32).pow(4);
+
+/// Number of keys covered by each occupancy word yielded by `BitSetCollection::blocks`.
+pub const BLOCK_BITS: u32 = 64;
+
+/// Convert a key into its bitset index, validating it against the `BitSet`'s capacity.
+fn key_index<K>(key: K) -> Result<u32, BitSetCollectionError>
+where
+    K: BitSetKey,
+{
+    let index = key.to_index().ok_or(BitSetCollectionError::KeyOutOfRange)?;
+
+    if index < BITSET_CAPACITY {
+        Ok(index)
+    } else {
+        Err(BitSetCollectionError::KeyBeyondCapacity(index))
+    }
+}
+
 /// `BitSetCollection` wrapping a `Vec`
-pub type BitSetVec<'a, K, V> = BitSetCollection<'a, K, Vec<V>>;
+pub type BitSetVec<'a, K, V, M = BitSet> = BitSetCollection<'a, K, Vec<V>, M>;
 /// `BitSetCollection` wrapping an immutable slice
-pub type BitSetSlice<'a, K, V> = BitSetCollection<'a, K, &'a [V]>;
+pub type BitSetSlice<'a, K, V, M = BitSet> = BitSetCollection<'a, K, &'a [V], M>;
 /// `BitSetCollection` wrapping a mutable slice
-pub type BitSetMutSlice<'a, K, V> = BitSetCollection<'a, K, &'a mut [V]>;
+pub type BitSetMutSlice<'a, K, V, M = BitSet> = BitSetCollection<'a, K, &'a mut [V], M>;
 /// `BitSetCollection` wrapping a `VecDeque`
-pub type BitSetVecDeque<'a, K, V> = BitSetCollection<'a, K, std::collections::VecDeque<V>>;
+pub type BitSetVecDeque<'a, K, V, M = BitSet> =
+    BitSetCollection<'a, K, alloc::collections::VecDeque<V>, M>;
 /// `BitSetCollection` wrapping a `BTreeMap`
-pub type BitSetBTreeMap<'a, K, V> = BitSetCollection<'a, K, std::collections::BTreeMap<K, V>>;
+pub type BitSetBTreeMap<'a, K, V, M = BitSet> =
+    BitSetCollection<'a, K, alloc::collections::BTreeMap<K, V>, M>;
 /// `BitSetCollection` wrapping a `HashMap`
-pub type BitSetHashMap<'a, K, V> = BitSetCollection<'a, K, std::collections::HashMap<K, V>>;
+pub type BitSetHashMap<'a, K, V, M = BitSet> =
+    BitSetCollection<'a, K, std::collections::HashMap<K, V>, M>;
 
 /// Wrapper for overriding a `Collection`'s key handling with a `BitSet`.
 ///
 /// Useful for accellerating lookups on map-like types, or to augment list-like types with distinct key tracking.
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
-pub struct BitSetCollection<'a, K, C>
+///
+/// Insertion and removal go through `StableCollection`, so a key's value never moves when other keys change.
+///
+/// Present keys are tracked in a `BitSetMask`, which defaults to `hibitset::BitSet`.
+#[derive(Debug, Clone)]
+pub struct BitSetCollection<'a, K, C, M = BitSet>
 where
     C: Collection<'a, K>,
 {
-    bitset: BitSet,
+    bitset: M,
     collection: C,
+    len: usize,
     _phantom: PhantomData<&'a K>,
 }
 
-impl<'a, C, K> BitSetCollection<'a, K, C>
+// Implemented by hand so that key types need not be `Default`.
+impl<'a, K, C, M> Default for BitSetCollection<'a, K, C, M>
 where
-    K: TryInto<u32>,
-    <K as TryInto<u32>>::Error: Debug,
-    C: for<'b> Collection<'b, K>,
+    C: Collection<'a, K> + Default,
+    M: Default,
 {
-    pub fn new(collection: C) -> Self {
+    fn default() -> Self {
+        BitSetCollection {
+            bitset: M::default(),
+            collection: C::default(),
+            len: 0,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+/// Collections are equal when they hold the same keys with equal values,
+/// regardless of what the wrapped collections store for absent keys.
+impl<'a, C, K, M, V> PartialEq for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: for<'b> Collection<'b, K, Item = V>,
+    M: BitSetLike,
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && (&self.bitset)
+                .iter()
+                .zip((&other.bitset).iter())
+                .all(|(index, other_index)| {
+                    let key = K::from_index(index);
+                    index == other_index
+                        && self.collection.get_unchecked(&key)
+                            == other.collection.get_unchecked(&key)
+                })
+    }
+}
+
+impl<'a, C, K, M, V> Eq for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: for<'b> Collection<'b, K, Item = V>,
+    M: BitSetLike,
+    V: Eq,
+{
+}
+
+/// Hashes the present keys and their values in ascending key order, consistent with `PartialEq`.
+impl<'a, C, K, M, V> Hash for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: for<'b> Collection<'b, K, Item = V>,
+    M: BitSetLike,
+    V: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        for index in (&self.bitset).iter() {
+            index.hash(state);
+            self.collection
+                .get_unchecked(&K::from_index(index))
+                .hash(state);
+        }
+    }
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetMask,
+{
+    /// Wrap `collection` without marking any of its keys as present.
+    pub fn with_collection(collection: C) -> Self
+    where
+        M: Default,
+    {
         BitSetCollection {
-            bitset: collection
-                .keys()
-                .map(|key| key.try_into().unwrap())
-                .collect(),
+            bitset: M::default(),
             collection,
+            len: 0,
             _phantom: Default::default(),
         }
     }
+
+    /// Number of keys present in the bitset.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Mark `index` as present, returning `true` if it already was.
+    fn add_index(&mut self, index: u32) -> bool {
+        let present = self.bitset.add(index);
+        if !present {
+            self.len += 1;
+        }
+        present
+    }
+
+    /// Mark `index` as absent, returning `true` if it was present.
+    fn remove_index(&mut self, index: u32) -> bool {
+        let present = self.bitset.remove(index);
+        if present {
+            self.len -= 1;
+        }
+        present
+    }
 }
 
-impl<'a, C, K, V> FromIterator<(K, V)> for BitSetCollection<'a, K, C>
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
 where
-    K: TryInto<u32>,
-    <K as TryInto<u32>>::Error: Debug,
-    C: Default + for<'b> Collection<'b, K, Item = V>,
+    K: BitSetKey,
+    C: for<'b> Collection<'b, K>,
+    M: BitSetMask + Default,
+{
+    /// Wrap `collection`, marking all of its existing keys in the bitset.
+    ///
+    /// Panics if any key cannot be represented as a bitset index.
+    pub fn new(collection: C) -> Self {
+        Self::try_new(collection).unwrap()
+    }
+
+    /// Wrap `collection`, marking all of its existing keys in the bitset.
+    pub fn try_new(collection: C) -> Result<Self, BitSetCollectionError> {
+        let indices = collection
+            .keys()
+            .map(key_index)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut collection = BitSetCollection::with_collection(collection);
+        for index in indices {
+            collection.add_index(index);
+        }
+        Ok(collection)
+    }
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetMask,
+{
+    /// Fetch the value at `key`, consulting the bitset before touching the wrapped collection.
+    pub fn try_get(&'a self, key: &K) -> Result<Option<&'a C::Item>, BitSetCollectionError> {
+        if self.bitset.contains(key_index(*key)?) {
+            Ok(Some(self.collection.get_unchecked(key)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Check whether `key` is present in the bitset.
+    pub fn try_contains_key(&self, key: &K) -> Result<bool, BitSetCollectionError> {
+        Ok(self.bitset.contains(key_index(*key)?))
+    }
+
+    /// Iterate over the values of present keys in ascending key order.
+    pub fn values(&'a self) -> BitSetCollectionValues<'a, K, C, M> {
+        BitSetCollectionValues::new(self)
+    }
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: StableCollection<'a, K>,
+    M: BitSetMask,
+{
+    /// Insert `value` at `key`, returning the previous value if the key was present.
+    ///
+    /// Fails without touching the bitset if the key is unrepresentable,
+    /// or if the wrapped collection is unable to store the value.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: C::Item,
+    ) -> Result<Option<C::Item>, BitSetCollectionError> {
+        let index = key_index(key)?;
+        let previous = self
+            .collection
+            .stable_insert(key, value)
+            .map_err(|_| BitSetCollectionError::InsertRejected(index))?;
+
+        if self.add_index(index) {
+            Ok(previous)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Remove and return the value at `key`, leaving the values at other keys in place.
+    pub fn try_remove(&mut self, key: &K) -> Result<Option<C::Item>, BitSetCollectionError> {
+        if self.remove_index(key_index(*key)?) {
+            Ok(self.collection.stable_remove(key))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Remove every key, resetting both the bitset and the wrapped collection.
+    pub fn clear(&mut self) {
+        self.bitset.clear();
+        self.len = 0;
+        self.collection.stable_clear();
+    }
+
+    /// Remove every key and yield the owned key/value pairs in ascending key order.
+    ///
+    /// Any pairs left undrained are dropped along with the iterator.
+    pub fn drain(&mut self) -> BitSetCollectionDrain<'a, '_, K, C, M>
+    where
+        M: Default,
+    {
+        BitSetCollectionDrain::new(self)
+    }
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Fetch a mutable reference to the value at `key`, consulting the bitset before touching the wrapped collection.
+    pub fn try_get_mut(&mut self, key: &K) -> Result<Option<&mut C::Item>, BitSetCollectionError> {
+        if self.bitset.contains(key_index(*key)?) {
+            Ok(self.collection.get_mut(key))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Fetch a mutable reference to the value at `key`.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut C::Item> {
+        self.try_get_mut(key).unwrap()
+    }
+
+    /// Iterate over present keys and mutable references to their values in ascending key order.
+    ///
+    /// Map-backed collections first build a sorted table of pointers to all of their values, see `CollectionMut::raw`.
+    pub fn iter_mut(&mut self) -> BitSetCollectionIterMut<'a, '_, K, C, M> {
+        BitSetCollectionIterMut::new(self)
+    }
+
+    /// Iterate over mutable references to the values of present keys in ascending key order.
+    ///
+    /// Map-backed collections first build a sorted table of pointers to all of their values, see `CollectionMut::raw`.
+    pub fn values_mut(&mut self) -> BitSetCollectionValuesMut<'a, '_, K, C, M> {
+        BitSetCollectionValuesMut::new(self)
+    }
+
+    /// Keep only the keys for which `f` returns `true`, removing the rest.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(K, &mut C::Item) -> bool,
+        M: Default,
+    {
+        let bitset = core::mem::take(&mut self.bitset);
+        self.len = 0;
+
+        for index in (&bitset).iter() {
+            let key = K::from_index(index);
+            let value = self
+                .collection
+                .get_mut(&key)
+                .expect("Key present in bitset but not in collection");
+
+            if f(key, value) {
+                self.add_index(index);
+            } else {
+                self.collection.stable_remove(&key);
+            }
+        }
+    }
+}
+
+impl<'a, C, K, M, V> FromIterator<(K, V)> for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Default + StableCollection<'a, K, Item = V>,
+    M: BitSetMask + Default,
 {
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
-        let mut collection: C = Default::default();
+        let mut collection = BitSetCollection::with_collection(C::default());
         for (key, value) in iter {
-            collection.insert(key, value);
+            collection.try_insert(key, value).unwrap();
         }
-        BitSetCollection::new(collection)
+        collection
     }
 }
 
-pub struct BitSetCollectionIterator<'a, K, C> {
-    key_iter: BitIter<BitSet>,
-    collection: &'a C,
-    _phantom: PhantomData<&'a K>,
+impl<'a, C, K, M, V> Extend<(K, V)> for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: StableCollection<'a, K, Item = V>,
+    M: BitSetMask,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.try_insert(key, value).unwrap();
+        }
+    }
 }
 
-impl<'a, K, C> BitSetCollectionIterator<'a, K, C>
+impl<'a, C, K, M> IntoIterator for BitSetCollection<'a, K, C, M>
 where
-    C: Collection<'a, K>,
+    K: BitSetKey,
+    C: StableCollection<'a, K>,
+    M: BitSetLike,
 {
-    pub fn new(collection: &'a BitSetCollection<'a, K, C>) -> Self {
-        let key_iter = collection.bitset.clone().iter();
-        let collection = &collection.collection;
+    type Item = (K, C::Item);
+    type IntoIter = BitSetCollectionIntoIter<'a, K, C, M>;
 
-        BitSetCollectionIterator {
-            key_iter,
-            collection,
-            _phantom: Default::default(),
-        }
+    fn into_iter(self) -> Self::IntoIter {
+        BitSetCollectionIntoIter::new(self)
     }
 }
 
-impl<'a, K, C> Iterator for BitSetCollectionIterator<'a, K, C>
+impl<'a, C, K, M> IntoIterator for &'a BitSetCollection<'a, K, C, M>
 where
+    K: BitSetKey,
     C: Collection<'a, K>,
-    K: Copy + TryInto<u32> + TryFrom<u32>,
-    <K as TryInto<u32>>::Error: Debug,
-    <K as TryFrom<u32>>::Error: Debug,
+    M: BitSetLike,
 {
     type Item = (K, &'a C::Item);
+    type IntoIter = BitSetCollectionIterator<'a, K, C, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BitSetCollectionIterator::new(self)
+    }
+}
+
+impl<'a, 'b, C, K, M> IntoIterator for &'b mut BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    C::Item: 'b,
+    M: BitSetLike,
+{
+    type Item = (K, &'b mut C::Item);
+    type IntoIter = BitSetCollectionIterMut<'a, 'b, K, C, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BitSetCollectionIterMut::new(self)
+    }
+}
+
+impl<'a, C, K, M, V> Index<K> for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: for<'b> Collection<'b, K, Item = V>,
+    M: BitSetLike,
+{
+    type Output = V;
+
+    /// Fetch the value at `key`.
+    ///
+    /// Panics if the key is not present.
+    fn index(&self, key: K) -> &V {
+        let index = present_index(&self.bitset, key);
+        self.collection
+            .get(&key)
+            .unwrap_or_else(|| panic!("key with index {} not present in collection", index))
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.key_iter.next().map(|key| {
-            let key: K = key.try_into().unwrap();
-            (key, self.collection.get_unchecked(&key))
-        })
+impl<'a, C, K, M, V> IndexMut<K> for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: for<'b> CollectionMut<'b, K, Item = V>,
+    M: BitSetLike,
+{
+    /// Fetch a mutable reference to the value at `key`.
+    ///
+    /// Panics if the key is not present.
+    fn index_mut(&mut self, key: K) -> &mut V {
+        let index = present_index(&self.bitset, key);
+        self.collection
+            .get_mut(&key)
+            .unwrap_or_else(|| panic!("key with index {} not present in collection", index))
     }
 }
 
-impl<'a, C, K> Collection<'a, K> for BitSetCollection<'a, K, C>
+/// Convert `key` into its bitset index for `Index` and `IndexMut`, panicking unless it is present in `bitset`.
+fn present_index<K, M>(bitset: &M, key: K) -> u32
 where
-    C: 'a + Collection<'a, K>,
-    K: Copy + TryInto<u32> + TryFrom<u32>,
-    <K as TryInto<u32>>::Error: Debug,
-    <K as TryFrom<u32>>::Error: Debug,
+    K: BitSetKey,
+    M: BitSetLike,
+{
+    let index = key_index(key).unwrap_or_else(|error| panic!("{}", error));
+    if !bitset.contains(index) {
+        panic!("key with index {} not present in bitset", index);
+    }
+    index
+}
+
+impl<'a, C, K, M> Collection<'a, K> for BitSetCollection<'a, K, C, M>
+where
+    C: 'a + StableCollection<'a, K>,
+    K: BitSetKey,
+    M: 'a + BitSetMask,
 {
     type Item = C::Item;
-    type Iter = BitSetCollectionIterator<'a, K, C>;
-    type KeyIter = std::iter::Map<BitIter<BitSet>, fn(u32) -> K>;
+    type Iter = BitSetCollectionIterator<'a, K, C, M>;
+    type KeyIter = core::iter::Map<BitIter<&'a M>, fn(u32) -> K>;
 
-    fn get(&'a self, key: &K) -> Option<&Self::Item> {
-        if self.bitset.contains((*key).try_into().unwrap()) {
-            Some(self.collection.get_unchecked(key))
-        } else {
-            None
-        }
+    fn get(&'a self, key: &K) -> Option<&'a Self::Item> {
+        self.try_get(key).unwrap()
     }
 
     fn insert(&mut self, key: K, value: Self::Item) -> Option<Self::Item> {
-        self.bitset.add(key.try_into().unwrap());
-        self.collection.insert(key, value)
+        self.try_insert(key, value).unwrap()
     }
 
     fn remove(&mut self, key: &K) -> Option<Self::Item> {
-        self.bitset.remove((*key).try_into().unwrap());
-        self.collection.remove(key)
+        self.try_remove(key).unwrap()
     }
 
     fn iter(&'a self) -> Self::Iter {
-        BitSetCollectionIterator::new(&self)
+        BitSetCollectionIterator::new(self)
     }
 
     fn keys(&'a self) -> Self::KeyIter {
-        self.bitset
-            .clone()
-            .iter()
-            .map(|key| key.try_into().unwrap())
+        (&self.bitset).iter().map(K::from_index)
     }
 
     fn contains_key(&'a self, key: &K) -> bool {
-        self.bitset.contains((*key).try_into().unwrap())
+        self.try_contains_key(key).unwrap()
     }
 }
 
@@ -192,4 +595,268 @@ mod tests {
         collection.remove(&2);
         assert!(!collection.contains_key(&2));
     }
+
+    #[test]
+    fn bitset_vec_iter_mut() {
+        let mut collection = BitSetVec::<usize, usize>::default();
+        collection.insert(0, 1);
+        collection.insert(2, 3);
+        collection.insert(4, 5);
+        for (key, value) in collection.iter_mut() {
+            *value += key;
+        }
+        *collection.get_mut(&0).unwrap() += 10;
+        assert_eq!(collection.get_mut(&1), None);
+        assert_eq!(
+            collection.values().copied().collect::<Vec<_>>(),
+            vec![11, 5, 9]
+        );
+    }
+
+    #[test]
+    fn bitset_btree_map_values_mut() {
+        let mut collection = vec![(0, 1), (2, 3), (4, 5)]
+            .into_iter()
+            .collect::<BitSetBTreeMap<usize, usize>>();
+        for value in collection.values_mut() {
+            *value *= 2;
+        }
+        assert_eq!(
+            collection.values().copied().collect::<Vec<_>>(),
+            vec![2, 6, 10]
+        );
+    }
+
+    /// Insert keys 0, 2 and 4, then remove 2 and check that the remaining keys keep their values.
+    macro_rules! check_insert_remove_get {
+        ($collection:expr) => {{
+            let collection = &mut $collection;
+            assert_eq!(collection.insert(0, 10), None);
+            assert_eq!(collection.insert(2, 12), None);
+            assert_eq!(collection.insert(4, 14), None);
+            assert_eq!(collection.insert(4, 24), Some(14));
+
+            assert_eq!(collection.remove(&2), Some(12));
+            assert_eq!(collection.remove(&2), None);
+            assert_eq!(collection.remove(&3), None);
+
+            assert_eq!(collection.get(&0), Some(&10));
+            assert_eq!(collection.get(&2), None);
+            assert_eq!(collection.get(&4), Some(&24));
+
+            assert_eq!(collection.insert(2, 22), None);
+            assert_eq!(collection.get(&2), Some(&22));
+            assert_eq!(collection.get(&4), Some(&24));
+        }};
+    }
+
+    #[test]
+    fn bitset_vec_key_stable() {
+        check_insert_remove_get!(BitSetVec::<usize, usize>::default());
+    }
+
+    #[test]
+    fn bitset_vec_deque_key_stable() {
+        check_insert_remove_get!(BitSetVecDeque::<usize, usize>::default());
+    }
+
+    #[test]
+    fn bitset_btree_map_key_stable() {
+        check_insert_remove_get!(BitSetBTreeMap::<usize, usize>::default());
+    }
+
+    #[test]
+    fn bitset_hash_map_key_stable() {
+        check_insert_remove_get!(BitSetHashMap::<usize, usize>::default());
+    }
+
+    #[test]
+    fn bitset_mut_slice_key_stable() {
+        let mut values = [0; 5];
+        let mut collection = BitSetMutSlice::<usize, usize>::with_collection(&mut values);
+        check_insert_remove_get!(collection);
+        assert_eq!(
+            collection.try_insert(5, 15),
+            Err(BitSetCollectionError::InsertRejected(5))
+        );
+        assert!(!collection.contains_key(&5));
+    }
+
+    #[test]
+    fn bitset_slice_key_stable() {
+        static VALUES: [usize; 5] = [10, 11, 12, 13, 14];
+        let mut collection = BitSetSlice::<usize, usize>::new(&VALUES);
+        assert_eq!(collection.remove(&2), Some(12));
+        assert_eq!(collection.remove(&2), None);
+        assert_eq!(collection.try_get(&1), Ok(Some(&11)));
+        assert_eq!(collection.try_get(&3), Ok(Some(&13)));
+        assert_eq!(
+            collection.try_insert(2, 22),
+            Err(BitSetCollectionError::InsertRejected(2))
+        );
+        assert!(!collection.contains_key(&2));
+    }
+
+    #[test]
+    fn bitset_vec_len_clear() {
+        let mut collection = BitSetVec::<usize, usize>::default();
+        assert!(collection.is_empty());
+        collection.insert(3, 3);
+        collection.insert(3, 4);
+        collection.insert(5, 5);
+        assert_eq!(collection.len(), 2);
+        collection.remove(&1);
+        collection.remove(&3);
+        assert_eq!(collection.len(), 1);
+        collection.clear();
+        assert!(collection.is_empty());
+        assert!(!collection.contains_key(&5));
+    }
+
+    #[test]
+    fn bitset_btree_map_retain_drain() {
+        let mut collection = (0..10)
+            .map(|key| (key, key * 10))
+            .collect::<BitSetBTreeMap<usize, usize>>();
+        collection.retain(|key, value| {
+            *value += 1;
+            key % 3 == 0
+        });
+        assert_eq!(collection.len(), 4);
+        assert_eq!(
+            collection.drain().collect::<Vec<_>>(),
+            vec![(0, 1), (3, 31), (6, 61), (9, 91)]
+        );
+        assert!(collection.is_empty());
+        assert_eq!(collection.iter().count(), 0);
+    }
+
+    #[test]
+    fn bitset_btree_map_try_insert_out_of_range() {
+        let mut collection = BitSetBTreeMap::<i64, f32>::default();
+        assert_eq!(
+            collection.try_insert(-1, 10.0),
+            Err(BitSetCollectionError::KeyOutOfRange)
+        );
+        assert_eq!(
+            collection.try_get(&-1),
+            Err(BitSetCollectionError::KeyOutOfRange)
+        );
+        assert_eq!(collection.try_insert(1, 10.0), Ok(None));
+        assert_eq!(collection.try_get(&1), Ok(Some(&10.0)));
+    }
+
+    #[test]
+    fn bitset_vec_try_insert_beyond_capacity() {
+        let mut collection = BitSetVec::<usize, f32>::default();
+        let key = BITSET_CAPACITY as usize;
+        assert_eq!(
+            collection.try_insert(key, 10.0),
+            Err(BitSetCollectionError::KeyBeyondCapacity(BITSET_CAPACITY))
+        );
+        assert_eq!(
+            collection.try_contains_key(&key),
+            Err(BitSetCollectionError::KeyBeyondCapacity(BITSET_CAPACITY))
+        );
+    }
+
+    #[test]
+    fn bitset_btree_map_atomic_mask() {
+        let mut collection = BitSetBTreeMap::<usize, usize, hibitset::AtomicBitSet>::default();
+        collection.insert(5, 50);
+        collection.insert(1, 10);
+        collection.insert(9, 90);
+        assert_eq!(collection.remove(&5), Some(50));
+        assert_eq!(collection.len(), 2);
+        assert_eq!(
+            collection.iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>(),
+            vec![(1, 10), (9, 90)]
+        );
+        collection.clear();
+        assert!(!collection.contains_key(&1));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn bitset_btree_map_derived_key() {
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, BitSetKey)]
+        struct EntityId(u32);
+
+        let mut collection = BitSetBTreeMap::<EntityId, &str>::default();
+        collection.insert(EntityId(4), "four");
+        collection.insert(EntityId(2), "two");
+        assert_eq!(
+            collection.keys().collect::<Vec<_>>(),
+            vec![EntityId(2), EntityId(4)]
+        );
+    }
+
+    #[test]
+    fn bitset_vec_into_iter_and_extend() {
+        let mut collection = BitSetVec::<usize, String>::default();
+        collection.extend(vec![(4, "d".to_string()), (1, "a".to_string())]);
+        collection.extend(Some((2, "b".to_string())));
+
+        for (key, value) in &mut collection {
+            value.push_str(&key.to_string());
+        }
+        let mut keys = Vec::new();
+        for (key, _) in &collection {
+            keys.push(key);
+        }
+        assert_eq!(keys, vec![1, 2, 4]);
+
+        assert_eq!(
+            collection.into_iter().collect::<Vec<_>>(),
+            vec![
+                (1, "a1".to_string()),
+                (2, "b2".to_string()),
+                (4, "d4".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn bitset_vec_index() {
+        let mut positions = vec![(0, 1.0), (3, 4.0)]
+            .into_iter()
+            .collect::<BitSetVec<usize, f32>>();
+        positions[3] += positions[0];
+        assert_eq!(positions[3], 5.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "key with index 1 not present in bitset")]
+    fn bitset_vec_index_absent() {
+        let positions = BitSetVec::<usize, f32>::with_collection(vec![0.0; 4]);
+        let _ = positions[1];
+    }
+
+    #[test]
+    fn bitset_vec_semantic_eq_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash(collection: &BitSetVec<usize, usize>) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            collection.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let mut padded = (0..6)
+            .map(|key| (key, key))
+            .collect::<BitSetVec<usize, usize>>();
+        padded.remove(&4);
+        padded.remove(&5);
+        let compact = (0..4)
+            .map(|key| (key, key))
+            .collect::<BitSetVec<usize, usize>>();
+        assert_eq!(padded, compact);
+        assert_eq!(hash(&padded), hash(&compact));
+
+        let mut other = compact.clone();
+        *other.get_mut(&2).unwrap() = 20;
+        assert_ne!(other, compact);
+        other.remove(&2);
+        assert_ne!(other, compact);
+    }
 }
@@ -0,0 +1,44 @@
+use hibitset::{AtomicBitSet, BitSet, BitSetLike};
+
+/// Mask tracking which keys of a `BitSetCollection` are present.
+///
+/// Membership checks and iteration come from `BitSetLike`,
+/// so any hierarchical bitset can be plugged in by providing the mutations below.
+pub trait BitSetMask: BitSetLike {
+    /// Mark `index` as present, returning `true` if it already was.
+    fn add(&mut self, index: u32) -> bool;
+
+    /// Mark `index` as absent, returning `true` if it was present.
+    fn remove(&mut self, index: u32) -> bool;
+
+    /// Mark every index as absent.
+    fn clear(&mut self);
+}
+
+impl BitSetMask for BitSet {
+    fn add(&mut self, index: u32) -> bool {
+        BitSet::add(self, index)
+    }
+
+    fn remove(&mut self, index: u32) -> bool {
+        BitSet::remove(self, index)
+    }
+
+    fn clear(&mut self) {
+        BitSet::clear(self)
+    }
+}
+
+impl BitSetMask for AtomicBitSet {
+    fn add(&mut self, index: u32) -> bool {
+        AtomicBitSet::add(self, index)
+    }
+
+    fn remove(&mut self, index: u32) -> bool {
+        AtomicBitSet::remove(self, index)
+    }
+
+    fn clear(&mut self) {
+        AtomicBitSet::clear(self)
+    }
+}
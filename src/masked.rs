@@ -0,0 +1,339 @@
+use hibitset::{BitIter, BitSetAnd, BitSetLike};
+
+use collection_trait::Collection;
+
+use crate::{
+    key_index, BitSetCollection, BitSetCollectionError, BitSetKey, CollectionMut, Join, JoinIter,
+    Joinable,
+};
+
+/// Iterator over the keys of a masked view in ascending order.
+pub type MaskedKeys<'b, K, M, N> = core::iter::Map<BitIter<BitSetAnd<&'b M, &'b N>>, fn(u32) -> K>;
+
+/// Read-only view of a `BitSetCollection` restricted to the keys also present in an external mask.
+///
+/// Useful for working on a subset of keys, such as those in a region or owned by a team,
+/// without copying their values out of the collection.
+#[derive(Debug)]
+pub struct Masked<'a, 'm, K, C, M, N>
+where
+    C: Collection<'a, K>,
+{
+    collection: &'a BitSetCollection<'a, K, C, M>,
+    mask: &'m N,
+}
+
+// Implemented by hand so that the view is `Copy` whatever its type parameters.
+impl<'a, 'm, K, C, M, N> Clone for Masked<'a, 'm, K, C, M, N>
+where
+    C: Collection<'a, K>,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, 'm, K, C, M, N> Copy for Masked<'a, 'm, K, C, M, N> where C: Collection<'a, K> {}
+
+impl<'a, 'm, K, C, M, N> Masked<'a, 'm, K, C, M, N>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetLike,
+    N: BitSetLike,
+{
+    /// Check whether `key` is present in both the bitset and the mask.
+    pub fn try_contains_key(&self, key: &K) -> Result<bool, BitSetCollectionError> {
+        let index = key_index(*key)?;
+        Ok(self.mask.contains(index) && self.collection.bitset.contains(index))
+    }
+
+    /// Check whether `key` is present in both the bitset and the mask.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.try_contains_key(key).unwrap()
+    }
+
+    /// Fetch the value at `key` if it is present in both the bitset and the mask.
+    pub fn try_get(&self, key: &K) -> Result<Option<&'a C::Item>, BitSetCollectionError> {
+        if self.try_contains_key(key)? {
+            Ok(Some(self.collection.collection.get_unchecked(key)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Fetch the value at `key` if it is present in both the bitset and the mask.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get(&self, key: &K) -> Option<&'a C::Item> {
+        self.try_get(key).unwrap()
+    }
+
+    /// Iterate over the keys present in both the bitset and the mask in ascending order.
+    pub fn keys(&self) -> MaskedKeys<'_, K, M, N> {
+        BitSetAnd(&self.collection.bitset, self.mask)
+            .iter()
+            .map(K::from_index as fn(u32) -> K)
+    }
+
+    /// Iterate over the keys present in both the bitset and the mask and their values in ascending key order.
+    pub fn iter(self) -> JoinIter<(Self,)> {
+        (self,).join()
+    }
+}
+
+impl<'a, 'm, K, C, M, N> Joinable for Masked<'a, 'm, K, C, M, N>
+where
+    C: Collection<'a, K>,
+    M: BitSetLike,
+    N: BitSetLike,
+{
+    type Key = K;
+    type Mask = BitSetAnd<&'a M, &'m N>;
+    type Value = &'a C;
+    type Item = &'a C::Item;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (
+            BitSetAnd(&self.collection.bitset, self.mask),
+            &self.collection.collection,
+        )
+    }
+
+    fn mask(self) -> Self::Mask {
+        BitSetAnd(&self.collection.bitset, self.mask)
+    }
+
+    unsafe fn fetch(value: &Self::Value, _: u32, key: K) -> Self::Item {
+        value.get_unchecked(&key)
+    }
+}
+
+/// Mutable view of a `BitSetCollection` restricted to the keys also present in an external mask.
+///
+/// Values can be modified in place, but keys can only be inserted or removed through the collection itself.
+#[derive(Debug)]
+pub struct MaskedMut<'a, 'b, 'm, K, C, M, N>
+where
+    C: Collection<'a, K>,
+{
+    collection: &'b mut BitSetCollection<'a, K, C, M>,
+    mask: &'m N,
+}
+
+impl<'a, 'b, 'm, K, C, M, N> MaskedMut<'a, 'b, 'm, K, C, M, N>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    M: BitSetLike,
+    N: BitSetLike,
+{
+    /// Check whether `key` is present in both the bitset and the mask.
+    pub fn try_contains_key(&self, key: &K) -> Result<bool, BitSetCollectionError> {
+        let index = key_index(*key)?;
+        Ok(self.mask.contains(index) && self.collection.bitset.contains(index))
+    }
+
+    /// Check whether `key` is present in both the bitset and the mask.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.try_contains_key(key).unwrap()
+    }
+
+    /// Fetch a mutable reference to the value at `key` if it is present in both the bitset and the mask.
+    pub fn try_get_mut(&mut self, key: &K) -> Result<Option<&mut C::Item>, BitSetCollectionError> {
+        if self.try_contains_key(key)? {
+            Ok(CollectionMut::get_mut(&mut self.collection.collection, key))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Fetch a mutable reference to the value at `key` if it is present in both the bitset and the mask.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut C::Item> {
+        self.try_get_mut(key).unwrap()
+    }
+
+    /// Iterate over the keys present in both the bitset and the mask in ascending order.
+    pub fn keys(&self) -> MaskedKeys<'_, K, M, N> {
+        BitSetAnd(&self.collection.bitset, self.mask)
+            .iter()
+            .map(K::from_index as fn(u32) -> K)
+    }
+
+    /// Iterate over the keys present in both the bitset and the mask
+    /// and mutable references to their values in ascending key order.
+    pub fn iter_mut(self) -> JoinIter<(Self,)>
+    where
+        C::Item: 'b,
+    {
+        (self,).join()
+    }
+}
+
+impl<'a, 'b, 'm, K, C, M, N, V> MaskedMut<'a, 'b, 'm, K, C, M, N>
+where
+    K: BitSetKey,
+    C: for<'s> CollectionMut<'s, K, Item = V>,
+    M: BitSetLike,
+    N: BitSetLike,
+{
+    /// Reborrow as a read-only view for the duration of a shared borrow.
+    fn as_masked(&self) -> Masked<'_, 'm, K, C, M, N> {
+        Masked {
+            collection: &*self.collection,
+            mask: self.mask,
+        }
+    }
+
+    /// Fetch the value at `key` if it is present in both the bitset and the mask.
+    pub fn try_get(&self, key: &K) -> Result<Option<&V>, BitSetCollectionError> {
+        self.as_masked().try_get(key)
+    }
+
+    /// Fetch the value at `key` if it is present in both the bitset and the mask.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.try_get(key).unwrap()
+    }
+
+    /// Iterate over the keys present in both the bitset and the mask and their values in ascending key order.
+    pub fn iter(&self) -> JoinIter<(Masked<'_, 'm, K, C, M, N>,)> {
+        self.as_masked().iter()
+    }
+}
+
+impl<'a, 'b, 'm, K, C, M, N> Joinable for MaskedMut<'a, 'b, 'm, K, C, M, N>
+where
+    C: CollectionMut<'a, K>,
+    C::Item: 'b,
+    M: BitSetLike,
+    N: BitSetLike,
+{
+    type Key = K;
+    type Mask = BitSetAnd<&'b M, &'m N>;
+    type Value = C::Raw;
+    type Item = &'b mut C::Item;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        let collection = self.collection;
+        (
+            BitSetAnd(&collection.bitset, self.mask),
+            collection.collection.raw(),
+        )
+    }
+
+    fn mask(self) -> Self::Mask {
+        BitSetAnd(&self.collection.bitset, self.mask)
+    }
+
+    unsafe fn fetch(value: &Self::Value, _: u32, key: K) -> Self::Item {
+        &mut *C::get_raw_mut(value, &key)
+    }
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    /// Borrow a read-only view of the keys present in both the bitset and `mask`.
+    pub fn masked<'m, N>(&'a self, mask: &'m N) -> Masked<'a, 'm, K, C, M, N>
+    where
+        N: BitSetLike,
+    {
+        Masked {
+            collection: self,
+            mask,
+        }
+    }
+
+    /// Borrow a mutable view of the keys present in both the bitset and `mask`.
+    pub fn masked_mut<'m, N>(&mut self, mask: &'m N) -> MaskedMut<'a, '_, 'm, K, C, M, N>
+    where
+        N: BitSetLike,
+    {
+        MaskedMut {
+            collection: self,
+            mask,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use collection_trait::Collection;
+    use hibitset::BitSet;
+
+    use crate::{BitSetVec, Join};
+
+    fn team(keys: &[u32]) -> BitSet {
+        keys.iter().copied().collect()
+    }
+
+    #[test]
+    fn masked_view_sees_intersection() {
+        let collection = (0..6)
+            .map(|key| (key, key * 10))
+            .collect::<BitSetVec<usize, usize>>();
+        let red = team(&[1, 3, 5, 7]);
+
+        let masked = collection.masked(&red);
+        assert!(masked.contains_key(&3));
+        assert!(!masked.contains_key(&2));
+        assert!(!masked.contains_key(&7));
+        assert_eq!(masked.get(&5), Some(&50));
+        assert_eq!(masked.get(&4), None);
+        assert_eq!(masked.keys().collect::<Vec<_>>(), vec![1, 3, 5]);
+        assert_eq!(
+            masked.iter().collect::<Vec<_>>(),
+            vec![(1, &10), (3, &30), (5, &50)]
+        );
+        assert_eq!(collection.len(), 6);
+    }
+
+    #[test]
+    fn masked_mut_view_modifies_intersection() {
+        let mut positions = (0..6)
+            .map(|key| (key, key as f32))
+            .collect::<BitSetVec<usize, f32>>();
+        let velocities = (0..6)
+            .map(|key| (key, 1.0))
+            .collect::<BitSetVec<usize, f32>>();
+        let blue = team(&[0, 2, 8]);
+
+        let mut masked = positions.masked_mut(&blue);
+        assert_eq!(masked.get_mut(&1), None);
+        *masked.get_mut(&2).unwrap() += 10.0;
+        assert_eq!(masked.keys().collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(masked.get(&2), Some(&12.0));
+        assert_eq!(masked.get(&3), None);
+        assert_eq!(
+            masked.iter().collect::<Vec<_>>(),
+            vec![(0, &0.0), (2, &12.0)]
+        );
+
+        for (_, position, velocity) in (masked, &velocities).join() {
+            *position += velocity;
+        }
+
+        assert_eq!(
+            positions.iter().map(|(_, v)| *v).collect::<Vec<_>>(),
+            vec![1.0, 1.0, 13.0, 3.0, 4.0, 5.0]
+        );
+
+        for (_, position) in positions.masked_mut(&blue).iter_mut() {
+            *position = 0.0;
+        }
+        assert_eq!(positions.get(&0), Some(&0.0));
+        assert_eq!(positions.get(&2), Some(&0.0));
+        assert_eq!(positions.get(&3), Some(&3.0));
+    }
+}
@@ -0,0 +1,488 @@
+use core::ops::{Bound, RangeBounds};
+
+use hibitset::{BitSet, BitSetLike};
+
+use collection_trait::Collection;
+
+use crate::{
+    key_index, BitSetCollection, BitSetCollectionBlockSlices, BitSetCollectionBlockSlicesMut,
+    BitSetCollectionBlocks, BitSetCollectionError, BitSetCollectionFreeKeys, BitSetCollectionRange,
+    BitSetKey, BITSET_CAPACITY, BLOCK_BITS,
+};
+
+const WORD_BITS: usize = BitSet::BITS_PER_USIZE;
+const LOG_WORD_BITS: u32 = WORD_BITS.trailing_zeros();
+const TOP_LAYER: u32 = 3;
+const WORDS_PER_BLOCK: usize = BLOCK_BITS as usize / WORD_BITS;
+
+/// Fetch word `word` of layer `layer`, where layer 0 holds the individual indices.
+fn layer_word<M>(mask: &M, layer: u32, word: usize) -> usize
+where
+    M: BitSetLike,
+{
+    match layer {
+        0 => mask.layer0(word),
+        1 => mask.layer1(word),
+        2 => mask.layer2(word),
+        _ => mask.layer3(),
+    }
+}
+
+/// Descend from a set bit at `position` in `layer` to the lowest or highest index beneath it.
+///
+/// Upper layers may over-approximate the indices beneath them, as `BitSetNot`'s do, so a word under a set bit
+/// can turn out empty. The search then has to resume past that word's span of indices, whose bound in the
+/// direction of the search is returned as `Err`: the index just after the span, or the first index of it.
+fn descend<M>(mask: &M, mut layer: u32, mut position: usize, lowest: bool) -> Result<u32, usize>
+where
+    M: BitSetLike,
+{
+    while layer > 0 {
+        layer -= 1;
+        let word = layer_word(mask, layer, position);
+        if word == 0 {
+            let shift = LOG_WORD_BITS * (layer + 1);
+            return Err(if lowest {
+                (position + 1) << shift
+            } else {
+                position << shift
+            });
+        }
+
+        let bit = if lowest {
+            word.trailing_zeros()
+        } else {
+            WORD_BITS as u32 - 1 - word.leading_zeros()
+        };
+        position = (position << LOG_WORD_BITS) | bit as usize;
+    }
+    Ok(position as u32)
+}
+
+/// Find the lowest index in `mask` at or after `start`.
+///
+/// Whole words of empty space are skipped by moving up a layer rather than testing each index.
+pub(crate) fn next_index<M>(mask: &M, start: u32) -> Option<u32>
+where
+    M: BitSetLike,
+{
+    let mut start = start as usize;
+    'search: loop {
+        if start >= BITSET_CAPACITY as usize {
+            return None;
+        }
+
+        let mut layer = 0;
+        let mut position = start;
+        loop {
+            let word_index = position >> LOG_WORD_BITS;
+            if layer == TOP_LAYER && word_index > 0 {
+                return None;
+            }
+
+            let bit = position & (WORD_BITS - 1);
+            let word = layer_word(mask, layer, word_index) & (!0 << bit);
+            if word != 0 {
+                let position = (word_index << LOG_WORD_BITS) | word.trailing_zeros() as usize;
+                match descend(mask, layer, position, true) {
+                    Ok(index) => return Some(index),
+                    Err(after) => {
+                        start = after;
+                        continue 'search;
+                    }
+                }
+            }
+
+            if layer == TOP_LAYER {
+                return None;
+            }
+            layer += 1;
+            position = word_index + 1;
+        }
+    }
+}
+
+/// Find the highest index in `mask` at or before `end`.
+pub(crate) fn prev_index<M>(mask: &M, end: u32) -> Option<u32>
+where
+    M: BitSetLike,
+{
+    let mut end = end.min(BITSET_CAPACITY - 1) as usize;
+    'search: loop {
+        let mut layer = 0;
+        let mut position = end;
+        loop {
+            let word_index = position >> LOG_WORD_BITS;
+            let bit = position & (WORD_BITS - 1);
+            let word = layer_word(mask, layer, word_index) & (!0 >> (WORD_BITS - 1 - bit));
+            if word != 0 {
+                let top = WORD_BITS - 1 - word.leading_zeros() as usize;
+                let position = (word_index << LOG_WORD_BITS) | top;
+                match descend(mask, layer, position, false) {
+                    Ok(index) => return Some(index),
+                    Err(0) => return None,
+                    Err(first) => {
+                        end = first - 1;
+                        continue 'search;
+                    }
+                }
+            }
+
+            if layer == TOP_LAYER || word_index == 0 {
+                return None;
+            }
+            layer += 1;
+            position = word_index - 1;
+        }
+    }
+}
+
+/// Find the lowest index in `start..end` present in `mask`'s bottom layer.
+///
+/// Unlike `next_index` this never consults the upper layers, which for masks like `BitSetNot` over-approximate
+/// the indices beneath them and would only lead the search into empty words, at the cost of visiting every word in the range.
+pub(crate) fn next_index_linear<M>(mask: &M, start: u32, end: u32) -> Option<u32>
+where
+    M: BitSetLike,
+{
+    let end = end.min(BITSET_CAPACITY) as usize;
+    let mut position = start as usize;
+    while position < end {
+        let word_index = position >> LOG_WORD_BITS;
+        let word = mask.layer0(word_index) & (!0 << (position & (WORD_BITS - 1)));
+        if word != 0 {
+            let index = (word_index << LOG_WORD_BITS) | word.trailing_zeros() as usize;
+            return Some(index as u32).filter(|_| index < end);
+        }
+        position = (word_index + 1) << LOG_WORD_BITS;
+    }
+    None
+}
+
+/// Find the lowest non-empty block of `BLOCK_BITS` indices at or after block `start`,
+/// returning its block number and occupancy word.
+///
+/// Empty blocks are skipped through the upper layers in the same way as `next_index`.
+pub(crate) fn next_block<M>(mask: &M, start: u32) -> Option<(u32, u64)>
+where
+    M: BitSetLike,
+{
+    let block = next_index(mask, start.checked_mul(BLOCK_BITS)?)? / BLOCK_BITS;
+    let first_word = block as usize * WORDS_PER_BLOCK;
+    let word = (0..WORDS_PER_BLOCK).fold(0, |word, i| {
+        word | (mask.layer0(first_word + i) as u64) << (i * WORD_BITS)
+    });
+    Some((block, word))
+}
+
+/// Convert the bounds of a key range into a half-open range of bitset indices.
+fn index_range<K, R>(range: R) -> Result<(u32, u32), BitSetCollectionError>
+where
+    K: BitSetKey,
+    R: RangeBounds<K>,
+{
+    let index = |key: &K| key.to_index().ok_or(BitSetCollectionError::KeyOutOfRange);
+
+    let start = match range.start_bound() {
+        Bound::Included(key) => index(key)?,
+        Bound::Excluded(key) => index(key)?.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(key) => index(key)?.saturating_add(1),
+        Bound::Excluded(key) => index(key)?,
+        Bound::Unbounded => BITSET_CAPACITY,
+    };
+
+    Ok((start, end.min(BITSET_CAPACITY)))
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetLike,
+{
+    /// Lowest present key.
+    pub fn first_key(&self) -> Option<K> {
+        next_index(&self.bitset, 0).map(K::from_index)
+    }
+
+    /// Highest present key.
+    pub fn last_key(&self) -> Option<K> {
+        prev_index(&self.bitset, BITSET_CAPACITY - 1).map(K::from_index)
+    }
+
+    /// Lowest present key strictly greater than `key`.
+    pub fn try_next_key_after(&self, key: &K) -> Result<Option<K>, BitSetCollectionError> {
+        Ok(next_index(&self.bitset, key_index(*key)? + 1).map(K::from_index))
+    }
+
+    /// Lowest present key strictly greater than `key`.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn next_key_after(&self, key: &K) -> Option<K> {
+        self.try_next_key_after(key).unwrap()
+    }
+
+    /// Highest present key strictly less than `key`.
+    pub fn try_prev_key_before(&self, key: &K) -> Result<Option<K>, BitSetCollectionError> {
+        match key_index(*key)? {
+            0 => Ok(None),
+            index => Ok(prev_index(&self.bitset, index - 1).map(K::from_index)),
+        }
+    }
+
+    /// Highest present key strictly less than `key`.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn prev_key_before(&self, key: &K) -> Option<K> {
+        self.try_prev_key_before(key).unwrap()
+    }
+
+    /// Iterate over the present keys within `range` and their values in ascending key order.
+    ///
+    /// Bounds beyond the bitset's capacity are clamped to it.
+    pub fn try_range<R>(
+        &'a self,
+        range: R,
+    ) -> Result<BitSetCollectionRange<'a, K, C, M>, BitSetCollectionError>
+    where
+        R: RangeBounds<K>,
+    {
+        let (start, end) = index_range(range)?;
+        Ok(BitSetCollectionRange::new(self, start, end))
+    }
+
+    /// Iterate over the present keys within `range` and their values in ascending key order.
+    ///
+    /// Panics if either bound cannot be represented as a bitset index.
+    pub fn range<R>(&'a self, range: R) -> BitSetCollectionRange<'a, K, C, M>
+    where
+        R: RangeBounds<K>,
+    {
+        self.try_range(range).unwrap()
+    }
+
+    /// Iterate over the keys within `range` that are absent from the bitset, in ascending order.
+    ///
+    /// Bounds beyond the bitset's capacity are clamped to it, and indices the key type cannot represent are skipped.
+    pub fn try_free_keys<R>(
+        &self,
+        range: R,
+    ) -> Result<BitSetCollectionFreeKeys<'_, K, M>, BitSetCollectionError>
+    where
+        R: RangeBounds<K>,
+    {
+        let (start, end) = index_range(range)?;
+        Ok(BitSetCollectionFreeKeys::new(&self.bitset, start, end))
+    }
+
+    /// Iterate over the keys within `range` that are absent from the bitset, in ascending order.
+    ///
+    /// Panics if either bound cannot be represented as a bitset index.
+    pub fn free_keys<R>(&self, range: R) -> BitSetCollectionFreeKeys<'_, K, M>
+    where
+        R: RangeBounds<K>,
+    {
+        self.try_free_keys(range).unwrap()
+    }
+
+    /// Iterate over the non-empty blocks of `BLOCK_BITS` keys in ascending order,
+    /// yielding the bitset index of each block's first key and a word with one bit per key in the block.
+    ///
+    /// Empty blocks are skipped through the bitset's upper layers without visiting their words.
+    pub fn blocks(&self) -> BitSetCollectionBlocks<'_, M> {
+        BitSetCollectionBlocks::new(&self.bitset)
+    }
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K> + AsRef<[<C as Collection<'a, K>>::Item]>,
+    M: BitSetLike,
+{
+    /// Iterate over the non-empty blocks of `BLOCK_BITS` keys as `blocks` does,
+    /// also yielding the slice of backing storage holding each block's values.
+    ///
+    /// Suited to batch processing, where full blocks can be handled in one go and sparse ones masked by their word.
+    pub fn block_slices(&'a self) -> BitSetCollectionBlockSlices<'a, C::Item, M> {
+        BitSetCollectionBlockSlices::new(&self.bitset, self.collection.as_ref())
+    }
+
+    /// Iterate over the non-empty blocks of `BLOCK_BITS` keys as `blocks` does,
+    /// also yielding the mutable slice of backing storage holding each block's values.
+    ///
+    /// Values of absent keys within a block are exposed too, and writing to them does not make their keys present.
+    pub fn block_slices_mut<'b>(&'b mut self) -> BitSetCollectionBlockSlicesMut<'b, C::Item, M>
+    where
+        C: AsMut<[<C as Collection<'a, K>>::Item]>,
+    {
+        BitSetCollectionBlockSlicesMut::new(&self.bitset, self.collection.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use collection_trait::Collection;
+    use hibitset::{BitSet, BitSetNot};
+
+    use crate::{BitSetBTreeMap, BitSetCollectionError, BitSetVec, BITSET_CAPACITY};
+
+    fn sparse() -> BitSetBTreeMap<'static, u32, u32> {
+        [3, 64, 70, 5_000, 300_000, BITSET_CAPACITY - 1]
+            .iter()
+            .map(|key| (*key, key * 2))
+            .collect()
+    }
+
+    #[test]
+    fn navigate_sparse_keys() {
+        let collection = sparse();
+        assert_eq!(collection.first_key(), Some(3));
+        assert_eq!(collection.last_key(), Some(BITSET_CAPACITY - 1));
+        assert_eq!(collection.next_key_after(&3), Some(64));
+        assert_eq!(collection.next_key_after(&70), Some(5_000));
+        assert_eq!(collection.next_key_after(&5_000), Some(300_000));
+        assert_eq!(collection.next_key_after(&(BITSET_CAPACITY - 1)), None);
+        assert_eq!(collection.prev_key_before(&300_000), Some(5_000));
+        assert_eq!(collection.prev_key_before(&64), Some(3));
+        assert_eq!(collection.prev_key_before(&3), None);
+        assert_eq!(
+            collection.try_next_key_after(&BITSET_CAPACITY),
+            Err(BitSetCollectionError::KeyBeyondCapacity(BITSET_CAPACITY))
+        );
+
+        let empty = BitSetBTreeMap::<u32, u32>::default();
+        assert_eq!(empty.first_key(), None);
+        assert_eq!(empty.last_key(), None);
+    }
+
+    #[test]
+    fn range_sparse_keys() {
+        let collection = sparse();
+        assert_eq!(
+            collection.range(64..=5_000).collect::<Vec<_>>(),
+            vec![(64, &128), (70, &140), (5_000, &10_000)]
+        );
+        assert_eq!(
+            collection
+                .range(65..5_000)
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>(),
+            vec![70]
+        );
+        assert_eq!(
+            collection
+                .range(300_000..)
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>(),
+            vec![300_000, BITSET_CAPACITY - 1]
+        );
+        assert_eq!(collection.range(..u32::MAX).count(), 6);
+        assert_eq!(collection.range(71..4_999).count(), 0);
+    }
+
+    #[test]
+    fn free_keys_sparse() {
+        let collection = sparse();
+        assert_eq!(
+            collection.free_keys(0..6).collect::<Vec<_>>(),
+            vec![0, 1, 2, 4, 5]
+        );
+        assert_eq!(
+            collection.free_keys(62..=71).collect::<Vec<_>>(),
+            vec![62, 63, 65, 66, 67, 68, 69, 71]
+        );
+        assert_eq!(
+            collection
+                .free_keys(BITSET_CAPACITY - 3..)
+                .collect::<Vec<_>>(),
+            vec![BITSET_CAPACITY - 3, BITSET_CAPACITY - 2]
+        );
+    }
+
+    #[test]
+    fn free_keys_within_key_range() {
+        let collection = BitSetBTreeMap::<NonZeroU32, ()>::default();
+        assert_eq!(
+            collection.free_keys(..).take(2).collect::<Vec<_>>(),
+            vec![NonZeroU32::new(1).unwrap(), NonZeroU32::new(2).unwrap()]
+        );
+
+        let collection = BitSetBTreeMap::<u8, ()>::default();
+        assert_eq!(
+            collection.free_keys(254..).collect::<Vec<_>>(),
+            vec![254, 255]
+        );
+    }
+
+    #[test]
+    fn blocks_sparse() {
+        let collection = sparse();
+        assert_eq!(
+            collection.blocks().collect::<Vec<_>>(),
+            vec![
+                (0, 1 << 3),
+                (64, 1 | 1 << 6),
+                (4_992, 1 << 8),
+                (299_968, 1 << 32),
+                (BITSET_CAPACITY - 64, 1 << 63),
+            ]
+        );
+        assert_eq!(BitSetBTreeMap::<u32, u32>::default().blocks().count(), 0);
+
+        let collection = [1, 2, 70]
+            .iter()
+            .map(|key| (NonZeroU32::new(*key).unwrap(), ()))
+            .collect::<BitSetBTreeMap<NonZeroU32, ()>>();
+        assert_eq!(
+            collection.blocks().collect::<Vec<_>>(),
+            vec![(0, 1 << 1 | 1 << 2), (64, 1 << 6)]
+        );
+    }
+
+    #[test]
+    fn block_slices_vec() {
+        let mut collection = [1, 2, 64, 130, 140]
+            .iter()
+            .map(|key| (*key, *key as u32))
+            .collect::<BitSetVec<usize, u32>>();
+
+        for (base, word, values) in collection.block_slices_mut() {
+            for (offset, value) in values.iter_mut().enumerate() {
+                if word & 1 << offset != 0 {
+                    *value += base;
+                }
+            }
+        }
+
+        let blocks = collection
+            .block_slices()
+            .map(|(base, word, values)| (base, word, values.len(), values[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            vec![
+                (0, 1 << 1 | 1 << 2, 64, 0),
+                (64, 1, 64, 128),
+                (128, 1 << 2 | 1 << 12, 13, 0),
+            ]
+        );
+        assert_eq!(collection.get(&130), Some(&258));
+        assert_eq!(collection.get(&2), Some(&2));
+    }
+
+    #[test]
+    fn over_approximate_layers_skip_empty_words() {
+        let occupied = (0..256).collect::<BitSet>();
+        let free = BitSetNot(&occupied);
+        assert_eq!(super::next_index(&free, 0), Some(256));
+        assert_eq!(super::next_index(&free, 100), Some(256));
+        assert_eq!(super::prev_index(&free, 255), None);
+        assert_eq!(super::prev_index(&free, 300), Some(300));
+    }
+}
@@ -0,0 +1,173 @@
+use hibitset::{BitParIter, BitSetLike};
+use rayon::iter::{plumbing::UnindexedConsumer, ParallelIterator};
+
+use collection_trait::Collection;
+
+use crate::{BitSetCollection, BitSetKey, DisjointCollectionMut, Join, Joinable, Maybe, Without};
+
+/// Marker for `Joinable` participants that can be fetched from several threads at once.
+///
+/// # Safety
+///
+/// Implementors must guarantee that their `Joinable::Value` can be shared across threads,
+/// and that fetching distinct keys concurrently is sound.
+pub unsafe trait ParJoinable: Joinable {}
+
+unsafe impl<'a, K, C, M> ParJoinable for &'a BitSetCollection<'a, K, C, M>
+where
+    C: Collection<'a, K> + Sync,
+    C::Item: Sync,
+    M: BitSetLike + Sync,
+{
+}
+
+unsafe impl<'a, 'b, K, C, M> ParJoinable for &'b mut BitSetCollection<'a, K, C, M>
+where
+    C: DisjointCollectionMut<'a, K>,
+    C::Item: 'b + Send,
+    M: BitSetLike + Sync,
+{
+}
+
+unsafe impl<J> ParJoinable for Without<J> where J: ParJoinable {}
+
+unsafe impl<J> ParJoinable for Maybe<J> where J: ParJoinable {}
+
+/// `Join` whose participants are all `ParJoinable`, and which can therefore be iterated in parallel.
+///
+/// # Safety
+///
+/// Only implemented for tuples of `ParJoinable` participants.
+pub unsafe trait ParJoin: Join {
+    /// Iterate over the keys present in every participant in parallel,
+    /// splitting work along the bitset's layers.
+    fn par_join(self) -> JoinParIter<Self> {
+        JoinParIter::new(self)
+    }
+}
+
+/// Parallel iterator over the items of a `ParJoin`.
+pub struct JoinParIter<J>
+where
+    J: Join,
+{
+    mask: J::Mask,
+    values: SyncValues<J::Values>,
+}
+
+impl<J> JoinParIter<J>
+where
+    J: ParJoin,
+{
+    pub fn new(join: J) -> Self {
+        let (mask, values) = join.open();
+        JoinParIter {
+            mask,
+            values: SyncValues(values),
+        }
+    }
+}
+
+/// Shares join values across threads.
+///
+/// Sound as long as every participant is `ParJoinable`.
+struct SyncValues<T>(T);
+
+unsafe impl<T> Send for SyncValues<T> {}
+unsafe impl<T> Sync for SyncValues<T> {}
+
+impl<J> ParallelIterator for JoinParIter<J>
+where
+    J: ParJoin,
+    J::Mask: Send + Sync,
+    J::Item: Send,
+{
+    type Item = J::Item;
+
+    fn drive_unindexed<Co>(self, consumer: Co) -> Co::Result
+    where
+        Co: UnindexedConsumer<Self::Item>,
+    {
+        let values = &self.values;
+        // Safety: `BitParIter` yields each index in the mask exactly once across all threads.
+        BitParIter::new(self.mask)
+            .map(move |index| unsafe { J::fetch(&values.0, index) })
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, C, K, M> BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: Collection<'a, K>,
+    M: BitSetLike + Sync,
+{
+    /// Iterate over present keys and their values in parallel.
+    pub fn par_iter(&'a self) -> JoinParIter<(&'a Self,)>
+    where
+        C: Sync,
+        C::Item: Sync,
+    {
+        (self,).par_join()
+    }
+
+    /// Iterate over present keys and mutable references to their values in parallel.
+    ///
+    /// Only available for backends where values at distinct keys can be mutated concurrently.
+    pub fn par_iter_mut(&mut self) -> JoinParIter<(&mut Self,)>
+    where
+        C: DisjointCollectionMut<'a, K>,
+        C::Item: Send,
+    {
+        (self,).par_join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use collection_trait::Collection;
+    use rayon::iter::ParallelIterator;
+
+    use crate::{BitSetBTreeMap, BitSetVec, Maybe, ParJoin};
+
+    #[test]
+    fn par_iter_mut_vec() {
+        let mut collection = BitSetVec::<usize, usize>::default();
+        for key in (0..10_000).step_by(3) {
+            collection.insert(key, key);
+        }
+
+        collection
+            .par_iter_mut()
+            .for_each(|(key, value)| *value += key);
+
+        assert!(collection.iter().all(|(key, value)| *value == key * 2));
+        assert_eq!(
+            collection
+                .par_iter()
+                .map(|(_, value)| *value)
+                .sum::<usize>(),
+            collection.values().sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn par_join_mixed_backends() {
+        let mut positions = BitSetVec::<usize, usize>::default();
+        let mut velocities = BitSetBTreeMap::<usize, usize>::default();
+        for key in 0..1_000 {
+            positions.insert(key, 0);
+        }
+        for key in (0..1_000).step_by(2) {
+            velocities.insert(key, key);
+        }
+
+        (&mut positions, Maybe(&velocities))
+            .par_join()
+            .for_each(|(_, position, velocity)| *position = velocity.copied().unwrap_or(1));
+
+        assert!(positions
+            .iter()
+            .all(|(key, value)| *value == if key % 2 == 0 { key } else { 1 }));
+    }
+}
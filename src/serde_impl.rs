@@ -0,0 +1,124 @@
+use core::{fmt::Formatter, marker::PhantomData};
+
+use hibitset::BitSetLike;
+use serde::{
+    de::{Error, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use collection_trait::Collection;
+
+use crate::{BitSetCollection, BitSetKey, BitSetMask, StableCollection};
+
+/// Serializes as a map containing only the keys present in the bitset.
+impl<'a, K, C, M, V> Serialize for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey + Serialize,
+    C: for<'b> Collection<'b, K, Item = V>,
+    M: BitSetLike,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        for index in (&self.bitset).iter() {
+            let key = K::from_index(index);
+            map.serialize_entry(&key, self.collection.get_unchecked(&key))?;
+        }
+        map.end()
+    }
+}
+
+/// Deserializes from a map, rebuilding the bitset from its keys.
+impl<'a, 'de, K, C, M, V> Deserialize<'de> for BitSetCollection<'a, K, C, M>
+where
+    K: BitSetKey + Deserialize<'de>,
+    C: Default + StableCollection<'a, K, Item = V>,
+    M: BitSetMask + Default,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(BitSetCollectionVisitor(PhantomData))
+    }
+}
+
+struct BitSetCollectionVisitor<'a, K, C, M>(PhantomData<BitSetCollection<'a, K, C, M>>)
+where
+    C: Collection<'a, K>;
+
+impl<'a, 'de, K, C, M, V> Visitor<'de> for BitSetCollectionVisitor<'a, K, C, M>
+where
+    K: BitSetKey + Deserialize<'de>,
+    C: Default + StableCollection<'a, K, Item = V>,
+    M: BitSetMask + Default,
+    V: Deserialize<'de>,
+{
+    type Value = BitSetCollection<'a, K, C, M>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        formatter.write_str("a map of bitset keys to values")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut collection = BitSetCollection::with_collection(C::default());
+
+        while let Some((key, value)) = access.next_entry::<K, V>()? {
+            collection
+                .try_insert(key, value)
+                .map_err(A::Error::custom)?;
+        }
+
+        Ok(collection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use collection_trait::Collection;
+
+    use crate::{BitSetBTreeMap, BitSetHashMap, BitSetVec};
+
+    #[test]
+    fn bitset_vec_round_trip() {
+        let mut collection = BitSetVec::<usize, f32>::default();
+        collection.insert(1, 1.0);
+        collection.insert(4, 4.0);
+
+        let json = serde_json::to_string(&collection).unwrap();
+        assert_eq!(json, r#"{"1":1.0,"4":4.0}"#);
+
+        let collection: BitSetVec<usize, f32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            collection.iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>(),
+            vec![(1, 1.0), (4, 4.0)]
+        );
+    }
+
+    #[test]
+    fn bitset_map_round_trip() {
+        let collection = vec![(3, 'c'), (0, 'a')]
+            .into_iter()
+            .collect::<BitSetHashMap<u32, char>>();
+
+        let json = serde_json::to_string(&collection).unwrap();
+        let collection: BitSetBTreeMap<u32, char> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            collection.iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>(),
+            vec![(0, 'a'), (3, 'c')]
+        );
+    }
+
+    #[test]
+    fn deserialize_out_of_range() {
+        assert!(serde_json::from_str::<BitSetBTreeMap<i64, char>>(r#"{"-1":"a"}"#).is_err());
+    }
+}
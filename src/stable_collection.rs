@@ -0,0 +1,149 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
+use core::hash::Hash;
+use std::collections::HashMap;
+
+use collection_trait::Collection;
+
+/// Extension of `Collection` with key-stable insertion and removal,
+/// used by `BitSetCollection` in place of the wrapped collection's own `insert` and `remove`.
+///
+/// Inserting or removing a key must never move the value stored at any other key.
+/// List-like collections therefore pad with `Default` values on insertion past their end,
+/// and leave a `Default` value behind on removal rather than shifting subsequent elements.
+pub trait StableCollection<'a, K>: Collection<'a, K> {
+    /// Store `value` at `key`, returning the value previously held in its slot.
+    ///
+    /// Returns `Err` with the value if the collection is unable to store it.
+    fn stable_insert(
+        &mut self,
+        key: K,
+        value: Self::Item,
+    ) -> Result<Option<Self::Item>, Self::Item>;
+
+    /// Take the value stored at `key` without disturbing any other key.
+    fn stable_remove(&mut self, key: &K) -> Option<Self::Item>;
+
+    /// Drop every stored value.
+    fn stable_clear(&mut self);
+}
+
+impl<'a, V> StableCollection<'a, usize> for Vec<V>
+where
+    V: Default,
+    Vec<V>: Collection<'a, usize, Item = V>,
+{
+    fn stable_insert(&mut self, key: usize, value: V) -> Result<Option<V>, V> {
+        if key >= self.len() {
+            self.resize_with(key + 1, Default::default);
+        }
+        Ok(Some(core::mem::replace(&mut self[key], value)))
+    }
+
+    fn stable_remove(&mut self, key: &usize) -> Option<V> {
+        self.as_mut_slice().get_mut(*key).map(core::mem::take)
+    }
+
+    fn stable_clear(&mut self) {
+        self.clear();
+    }
+}
+
+impl<'a, 'b, V> StableCollection<'a, usize> for &'b [V]
+where
+    V: Clone,
+    &'b [V]: Collection<'a, usize, Item = V>,
+{
+    fn stable_insert(&mut self, _: usize, value: V) -> Result<Option<V>, V> {
+        Err(value)
+    }
+
+    /// Immutable slices cannot give up their values, so removal returns a clone.
+    fn stable_remove(&mut self, key: &usize) -> Option<V> {
+        <[V]>::get(self, *key).cloned()
+    }
+
+    fn stable_clear(&mut self) {}
+}
+
+impl<'a, 'b, V> StableCollection<'a, usize> for &'b mut [V]
+where
+    V: Default,
+    &'b mut [V]: Collection<'a, usize, Item = V>,
+{
+    fn stable_insert(&mut self, key: usize, value: V) -> Result<Option<V>, V> {
+        match <[V]>::get_mut(self, key) {
+            Some(slot) => Ok(Some(core::mem::replace(slot, value))),
+            None => Err(value),
+        }
+    }
+
+    fn stable_remove(&mut self, key: &usize) -> Option<V> {
+        <[V]>::get_mut(self, *key).map(core::mem::take)
+    }
+
+    fn stable_clear(&mut self) {
+        for value in self.iter_mut() {
+            *value = V::default();
+        }
+    }
+}
+
+impl<'a, V> StableCollection<'a, usize> for VecDeque<V>
+where
+    V: Default,
+    VecDeque<V>: Collection<'a, usize, Item = V>,
+{
+    fn stable_insert(&mut self, key: usize, value: V) -> Result<Option<V>, V> {
+        if key >= self.len() {
+            self.resize_with(key + 1, Default::default);
+        }
+        Ok(Some(core::mem::replace(&mut self[key], value)))
+    }
+
+    fn stable_remove(&mut self, key: &usize) -> Option<V> {
+        VecDeque::get_mut(self, *key).map(core::mem::take)
+    }
+
+    fn stable_clear(&mut self) {
+        self.clear();
+    }
+}
+
+impl<'a, K, V> StableCollection<'a, K> for BTreeMap<K, V>
+where
+    K: Ord,
+    BTreeMap<K, V>: Collection<'a, K, Item = V>,
+{
+    fn stable_insert(&mut self, key: K, value: V) -> Result<Option<V>, V> {
+        Ok(BTreeMap::insert(self, key, value))
+    }
+
+    fn stable_remove(&mut self, key: &K) -> Option<V> {
+        BTreeMap::remove(self, key)
+    }
+
+    fn stable_clear(&mut self) {
+        self.clear();
+    }
+}
+
+impl<'a, K, V> StableCollection<'a, K> for HashMap<K, V>
+where
+    K: Eq + Hash,
+    HashMap<K, V>: Collection<'a, K, Item = V>,
+{
+    fn stable_insert(&mut self, key: K, value: V) -> Result<Option<V>, V> {
+        Ok(HashMap::insert(self, key, value))
+    }
+
+    fn stable_remove(&mut self, key: &K) -> Option<V> {
+        HashMap::remove(self, key)
+    }
+
+    fn stable_clear(&mut self) {
+        self.clear();
+    }
+}
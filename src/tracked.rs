@@ -0,0 +1,269 @@
+use core::ops::Deref;
+
+use hibitset::{BitSet, BitSetLike};
+
+use collection_trait::Collection;
+
+use crate::{
+    key_index, BitSetCollection, BitSetCollectionError, BitSetKey, BitSetMask, CollectionMut,
+    StableCollection,
+};
+
+/// Keys of a `Tracked` collection that changed since its changes were last taken.
+///
+/// Changes are coalesced so that each key appears in at most one set:
+/// a key inserted and then modified is only `inserted`, a key inserted and then removed disappears entirely,
+/// and a key removed and then inserted again is `modified`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Changes {
+    inserted: BitSet,
+    modified: BitSet,
+    removed: BitSet,
+}
+
+impl Changes {
+    /// Indices of keys that were absent before and are present now.
+    pub fn inserted(&self) -> &BitSet {
+        &self.inserted
+    }
+
+    /// Indices of keys that were present before and may hold a different value now.
+    pub fn modified(&self) -> &BitSet {
+        &self.modified
+    }
+
+    /// Indices of keys that were present before and are absent now.
+    pub fn removed(&self) -> &BitSet {
+        &self.removed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    fn mark_inserted(&mut self, index: u32) {
+        if self.removed.remove(index) {
+            self.modified.add(index);
+        } else {
+            self.inserted.add(index);
+        }
+    }
+
+    fn mark_modified(&mut self, index: u32) {
+        if !self.inserted.contains(index) {
+            self.modified.add(index);
+        }
+    }
+
+    fn mark_removed(&mut self, index: u32) {
+        self.modified.remove(index);
+        if !self.inserted.remove(index) {
+            self.removed.add(index);
+        }
+    }
+}
+
+/// `BitSetCollection` wrapper recording which keys are inserted, modified and removed.
+///
+/// Reads go through `Deref` to the wrapped collection,
+/// while mutation is only possible through the tracking methods below.
+#[derive(Debug, Clone)]
+pub struct Tracked<'a, K, C, M = BitSet>
+where
+    C: Collection<'a, K>,
+{
+    collection: BitSetCollection<'a, K, C, M>,
+    changes: Changes,
+}
+
+impl<'a, K, C, M> Default for Tracked<'a, K, C, M>
+where
+    C: Collection<'a, K> + Default,
+    M: Default,
+{
+    fn default() -> Self {
+        Tracked {
+            collection: Default::default(),
+            changes: Default::default(),
+        }
+    }
+}
+
+impl<'a, K, C, M> Deref for Tracked<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+{
+    type Target = BitSetCollection<'a, K, C, M>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.collection
+    }
+}
+
+impl<'a, K, C, M> Tracked<'a, K, C, M>
+where
+    C: Collection<'a, K>,
+    M: BitSetMask,
+{
+    /// Track changes to `collection` from this point on.
+    pub fn new(collection: BitSetCollection<'a, K, C, M>) -> Self {
+        Tracked {
+            collection,
+            changes: Default::default(),
+        }
+    }
+
+    /// Stop tracking and return the wrapped collection.
+    pub fn into_inner(self) -> BitSetCollection<'a, K, C, M> {
+        self.collection
+    }
+
+    /// Changes recorded since they were last taken.
+    pub fn changes(&self) -> &Changes {
+        &self.changes
+    }
+
+    /// Return the changes recorded so far and start recording afresh.
+    pub fn take_changes(&mut self) -> Changes {
+        core::mem::take(&mut self.changes)
+    }
+}
+
+impl<'a, K, C, M> Tracked<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: StableCollection<'a, K>,
+    M: BitSetMask,
+{
+    /// Insert `value` at `key`, recording it as inserted or modified.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: C::Item,
+    ) -> Result<Option<C::Item>, BitSetCollectionError> {
+        let index = key_index(key)?;
+        let present = self.collection.bitset.contains(index);
+        let previous = self.collection.try_insert(key, value)?;
+
+        if present {
+            self.changes.mark_modified(index);
+        } else {
+            self.changes.mark_inserted(index);
+        }
+        Ok(previous)
+    }
+
+    /// Insert `value` at `key`, recording it as inserted or modified.
+    ///
+    /// Panics under the same conditions as `try_insert` fails.
+    pub fn insert(&mut self, key: K, value: C::Item) -> Option<C::Item> {
+        self.try_insert(key, value).unwrap()
+    }
+
+    /// Remove and return the value at `key`, recording it as removed if it was present.
+    pub fn try_remove(&mut self, key: &K) -> Result<Option<C::Item>, BitSetCollectionError> {
+        let index = key_index(*key)?;
+        let present = self.collection.bitset.contains(index);
+        let value = self.collection.try_remove(key)?;
+
+        if present {
+            self.changes.mark_removed(index);
+        }
+        Ok(value)
+    }
+
+    /// Remove and return the value at `key`, recording it as removed if it was present.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn remove(&mut self, key: &K) -> Option<C::Item> {
+        self.try_remove(key).unwrap()
+    }
+
+    /// Remove every key, recording each as removed.
+    pub fn clear(&mut self) {
+        for index in (&self.collection.bitset).iter() {
+            self.changes.mark_removed(index);
+        }
+        self.collection.clear();
+    }
+}
+
+impl<'a, K, C, M> Tracked<'a, K, C, M>
+where
+    K: BitSetKey,
+    C: CollectionMut<'a, K>,
+    M: BitSetMask,
+{
+    /// Fetch a mutable reference to the value at `key`, recording it as modified if present.
+    pub fn try_get_mut(&mut self, key: &K) -> Result<Option<&mut C::Item>, BitSetCollectionError> {
+        let index = key_index(*key)?;
+        if self.collection.bitset.contains(index) {
+            self.changes.mark_modified(index);
+        }
+        self.collection.try_get_mut(key)
+    }
+
+    /// Fetch a mutable reference to the value at `key`, recording it as modified if present.
+    ///
+    /// Panics if the key cannot be represented as a bitset index.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut C::Item> {
+        self.try_get_mut(key).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use collection_trait::Collection;
+    use hibitset::{BitSet, BitSetLike};
+
+    use crate::{BitSetBTreeMap, Tracked};
+
+    fn indices(bitset: &BitSet) -> Vec<u32> {
+        bitset.iter().collect()
+    }
+
+    #[test]
+    fn tracked_vec_changes() {
+        let mut collection = Tracked::<usize, Vec<usize>>::default();
+        collection.insert(0, 0);
+        collection.insert(1, 1);
+        collection.insert(2, 2);
+        *collection.get_mut(&1).unwrap() += 10;
+
+        let changes = collection.take_changes();
+        assert_eq!(indices(changes.inserted()), vec![0, 1, 2]);
+        assert!(changes.modified().is_empty());
+        assert!(collection.changes().is_empty());
+
+        *collection.get_mut(&0).unwrap() += 1;
+        collection.insert(2, 20);
+        collection.remove(&1);
+        collection.insert(3, 3);
+        collection.remove(&3);
+        assert_eq!(collection.get_mut(&5), None);
+
+        let changes = collection.take_changes();
+        assert!(changes.inserted().is_empty());
+        assert_eq!(indices(changes.modified()), vec![0, 2]);
+        assert_eq!(indices(changes.removed()), vec![1]);
+        assert_eq!(collection.get(&2), Some(&20));
+    }
+
+    #[test]
+    fn tracked_btree_map_reinsert_and_clear() {
+        let collection = vec![(1, 'a'), (4, 'b')]
+            .into_iter()
+            .collect::<BitSetBTreeMap<usize, char>>();
+        let mut collection = Tracked::new(collection);
+
+        collection.remove(&1);
+        collection.insert(1, 'c');
+        let changes = collection.take_changes();
+        assert_eq!(indices(changes.modified()), vec![1]);
+        assert!(changes.removed().is_empty());
+
+        collection.clear();
+        assert_eq!(indices(collection.changes().removed()), vec![1, 4]);
+        assert!(collection.into_inner().is_empty());
+    }
+}